@@ -0,0 +1,16 @@
+use bmkgw::cuaca::Province;
+use bmkgw::exporter::Exporter;
+use bmkgw::Error;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let addr = ([127, 0, 0, 1], 9100).into();
+    let exporter = Exporter::new(
+        vec![Province::DKI, Province::JawaBarat],
+        Duration::from_secs(300),
+    );
+
+    println!("serving metrics on http://{}/metrics", addr);
+    exporter.run(addr).await
+}
@@ -0,0 +1,13 @@
+use bmkgw::cache::Cache;
+use bmkgw::{server, Error};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let addr = ([127, 0, 0, 1], 8000).into();
+    let cache = Arc::new(Cache::new(Duration::from_secs(300)));
+
+    println!("listening on http://{}", addr);
+    server::serve(addr, cache).await
+}
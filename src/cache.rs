@@ -0,0 +1,135 @@
+use crate::cuaca::{self, Data, Province};
+use crate::gempa::{self, Gempa, Url};
+use crate::Error;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+struct Entry<T> {
+    fetched_at: Instant,
+    data: Arc<T>,
+}
+
+struct Store<K, T> {
+    entries: Mutex<HashMap<K, Entry<T>>>,
+}
+
+impl<K: Eq + Hash, T> Store<K, T> {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &K, ttl: Duration) -> Option<Arc<T>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|e| e.fetched_at.elapsed() < ttl)
+            .map(|e| e.data.clone())
+    }
+
+    fn insert(&self, key: K, data: T) -> Arc<T> {
+        let data = Arc::new(data);
+        let entry = Entry {
+            fetched_at: Instant::now(),
+            data: data.clone(),
+        };
+        self.entries.lock().unwrap().insert(key, entry);
+        data
+    }
+
+    fn age(&self, key: &K) -> Option<Duration> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key).map(|e| e.fetched_at.elapsed())
+    }
+}
+
+/// In-memory cache in front of [`gempa::get_data`] and [`cuaca::get_data`].
+///
+/// Entries older than the time-to-live are fetched again on the next access.
+/// Concurrent misses for the same key may fetch more than once.
+pub struct Cache {
+    ttl: Duration,
+    gempa: Store<Url, Vec<Gempa>>,
+    cuaca: Store<Province, Data>,
+}
+
+impl Cache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            gempa: Store::new(),
+            cuaca: Store::new(),
+        }
+    }
+
+    pub async fn gempa(&self, url: Url) -> Result<Arc<Vec<Gempa>>, Error> {
+        if let Some(data) = self.gempa.get(&url, self.ttl) {
+            return Ok(data);
+        }
+
+        let data = gempa::get_data(url).await?;
+        Ok(self.gempa.insert(url, data))
+    }
+
+    pub async fn cuaca(&self, p: Province) -> Result<Arc<Data>, Error> {
+        if let Some(data) = self.cuaca.get(&p, self.ttl) {
+            return Ok(data);
+        }
+
+        let data = cuaca::get_data(p).await?;
+        Ok(self.cuaca.insert(p, data))
+    }
+
+    /// Stores data obtained elsewhere, e.g. parsed from an archived file.
+    pub fn insert_gempa(&self, url: Url, data: Vec<Gempa>) {
+        self.gempa.insert(url, data);
+    }
+
+    pub fn insert_cuaca(&self, p: Province, data: Data) {
+        self.cuaca.insert(p, data);
+    }
+
+    /// Time since the feed was last fetched or inserted.
+    pub fn gempa_age(&self, url: Url) -> Option<Duration> {
+        self.gempa.age(&url)
+    }
+
+    pub fn cuaca_age(&self, p: Province) -> Option<Duration> {
+        self.cuaca.age(&p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+
+    #[tokio::test]
+    async fn cache_hit_test() {
+        let cache = Cache::new(Duration::from_secs(60));
+        let quakes = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        cache.insert_gempa(Url::GempaTerkini, quakes);
+
+        let data = cache.gempa(Url::GempaTerkini).await.unwrap();
+
+        assert_eq!(data.len(), 8);
+        assert!(cache.gempa_age(Url::GempaTerkini).unwrap() < Duration::from_secs(60));
+        assert!(cache.gempa_age(Url::Autogempa).is_none());
+    }
+
+    #[test]
+    fn store_expiry_test() {
+        let store: Store<u8, &str> = Store::new();
+        store.insert(1, "a");
+
+        assert_eq!(
+            store.get(&1, Duration::from_secs(60)).as_deref(),
+            Some(&"a")
+        );
+        assert!(store.get(&1, Duration::from_secs(0)).is_none());
+        assert!(store.get(&2, Duration::from_secs(60)).is_none());
+    }
+}
@@ -1,9 +1,31 @@
-use crate::{Error, BMKG_BASE_URL};
-use quick_xml::events::Event;
+use crate::{geo, Error, TimeZone, BMKG_BASE_URL};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone as _, Utc};
+use futures_util::{Stream, TryStreamExt};
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use std::borrow::Borrow;
+use std::fs::File;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncRead;
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::io::{StreamReader, SyncIoBridge};
+
+pub mod alert;
+#[cfg(feature = "sqlite")]
+pub mod archive;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod comfort;
+pub mod csv;
+pub mod diff;
+pub mod ics;
+pub mod summary;
+pub mod units;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Domain {
@@ -22,6 +44,7 @@ impl Domain {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Province {
     Aceh,
     Bali,
@@ -61,6 +84,45 @@ pub enum Province {
 }
 
 impl Province {
+    pub fn all() -> Vec<Province> {
+        vec![
+            Self::Aceh,
+            Self::Bali,
+            Self::BangkaBelitung,
+            Self::Banten,
+            Self::Bengkulu,
+            Self::DIY,
+            Self::DKI,
+            Self::Gorontalo,
+            Self::Jambi,
+            Self::JawaBarat,
+            Self::JawaTengah,
+            Self::JawaTimur,
+            Self::Kalbar,
+            Self::Kalsel,
+            Self::Kalteng,
+            Self::Kaltim,
+            Self::Kaltara,
+            Self::KepulauanRiau,
+            Self::Lampung,
+            Self::Maluku,
+            Self::MalukuUtara,
+            Self::NTB,
+            Self::NTT,
+            Self::Papua,
+            Self::PapuaBarat,
+            Self::Riau,
+            Self::SulawesiBarat,
+            Self::SulawesiSelatan,
+            Self::SulawesiTengah,
+            Self::SulawesiTenggara,
+            Self::SulawesiUtara,
+            Self::SumateraBarat,
+            Self::SumateraSelatan,
+            Self::SumateraUtara,
+            Self::Indonesia,
+        ]
+    }
     fn build_url(s: &str) -> String {
         let s = Domain::get_data()
             .into_iter()
@@ -72,45 +134,50 @@ impl Province {
             BMKG_BASE_URL, s.url_param
         )
     }
-    pub fn to_url(&self) -> String {
+    /// Key used by `from_str` and `Domain::value`, e.g. "jabar".
+    pub fn key(&self) -> &'static str {
         match self {
-            Self::Aceh => Self::build_url("aceh"),
-            Self::Bali => Self::build_url("bali"),
-            Self::BangkaBelitung => Self::build_url("bangka_belitung"),
-            Self::Banten => Self::build_url("banten"),
-            Self::Bengkulu => Self::build_url("bengkulu"),
-            Self::DIY => Self::build_url("diy"),
-            Self::DKI => Self::build_url("dki"),
-            Self::Gorontalo => Self::build_url("gorontalo"),
-            Self::Jambi => Self::build_url("jambi"),
-            Self::JawaBarat => Self::build_url("jabar"),
-            Self::JawaTengah => Self::build_url("jateng"),
-            Self::JawaTimur => Self::build_url("jatim"),
-            Self::Kalbar => Self::build_url("kalbar"),
-            Self::Kalsel => Self::build_url("kalsel"),
-            Self::Kalteng => Self::build_url("kalteng"),
-            Self::Kaltim => Self::build_url("kaltim"),
-            Self::Kaltara => Self::build_url("kaltara"),
-            Self::KepulauanRiau => Self::build_url("kepri"),
-            Self::Lampung => Self::build_url("lampung"),
-            Self::Maluku => Self::build_url("maluku"),
-            Self::MalukuUtara => Self::build_url("maluku_utara"),
-            Self::NTB => Self::build_url("ntb"),
-            Self::NTT => Self::build_url("ntt"),
-            Self::Papua => Self::build_url("papua"),
-            Self::PapuaBarat => Self::build_url("papua_barat"),
-            Self::Riau => Self::build_url("riau"),
-            Self::SulawesiBarat => Self::build_url("sulawesi_barat"),
-            Self::SulawesiSelatan => Self::build_url("sulawesi_selatan"),
-            Self::SulawesiTengah => Self::build_url("sulawesi_tengah"),
-            Self::SulawesiTenggara => Self::build_url("sulawesi_tenggara"),
-            Self::SulawesiUtara => Self::build_url("sulawesi_utara"),
-            Self::SumateraBarat => Self::build_url("sumatera_barat"),
-            Self::SumateraSelatan => Self::build_url("sumatera_selatan"),
-            Self::SumateraUtara => Self::build_url("sumatera_utara"),
-            Self::Indonesia => Self::build_url("indonesia"),
+            Self::Aceh => "aceh",
+            Self::Bali => "bali",
+            Self::BangkaBelitung => "bangka_belitung",
+            Self::Banten => "banten",
+            Self::Bengkulu => "bengkulu",
+            Self::DIY => "diy",
+            Self::DKI => "dki",
+            Self::Gorontalo => "gorontalo",
+            Self::Jambi => "jambi",
+            Self::JawaBarat => "jabar",
+            Self::JawaTengah => "jateng",
+            Self::JawaTimur => "jatim",
+            Self::Kalbar => "kalbar",
+            Self::Kalsel => "kalsel",
+            Self::Kalteng => "kalteng",
+            Self::Kaltim => "kaltim",
+            Self::Kaltara => "kaltara",
+            Self::KepulauanRiau => "kepri",
+            Self::Lampung => "lampung",
+            Self::Maluku => "maluku",
+            Self::MalukuUtara => "maluku_utara",
+            Self::NTB => "ntb",
+            Self::NTT => "ntt",
+            Self::Papua => "papua",
+            Self::PapuaBarat => "papua_barat",
+            Self::Riau => "riau",
+            Self::SulawesiBarat => "sulawesi_barat",
+            Self::SulawesiSelatan => "sulawesi_selatan",
+            Self::SulawesiTengah => "sulawesi_tengah",
+            Self::SulawesiTenggara => "sulawesi_tenggara",
+            Self::SulawesiUtara => "sulawesi_utara",
+            Self::SumateraBarat => "sumatera_barat",
+            Self::SumateraSelatan => "sumatera_selatan",
+            Self::SumateraUtara => "sumatera_utara",
+            Self::Indonesia => "indonesia",
         }
     }
+    pub fn to_url(&self) -> String {
+        Self::build_url(self.key())
+    }
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str<T: Borrow<str>>(s: T) -> Option<Self> {
         match s.borrow().to_lowercase().as_ref() {
             "aceh" => Some(Self::Aceh),
@@ -144,7 +211,7 @@ impl Province {
             "sulawesi_tengah" => Some(Self::SulawesiTengah),
             "sulawesi_tenggara" => Some(Self::SulawesiTenggara),
             "sulawesi_utara" => Some(Self::SulawesiUtara),
-            "sumatera_barat" => Some(Self::SumateraUtara),
+            "sumatera_barat" => Some(Self::SumateraBarat),
             "sumatera_selatan" => Some(Self::SumateraSelatan),
             "sumatera_utara" => Some(Self::SumateraUtara),
             "indonesia" => Some(Self::Indonesia),
@@ -154,6 +221,7 @@ impl Province {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct Value {
     pub unit: Option<String>,
     pub value: Option<String>,
@@ -166,13 +234,18 @@ impl Value {
             value: None,
         }
     }
+    pub fn as_f64(&self) -> Option<f64> {
+        self.value.as_ref().and_then(|v| v.trim().parse().ok())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct TimeRange {
     #[serde(rename = "type", default)]
     pub typ: Option<String>,
     pub h: Option<String>,
+    pub day: Option<String>,
     pub datetime: Option<String>,
     pub values: Vec<Value>,
     // value: Option<String>,
@@ -183,13 +256,35 @@ impl TimeRange {
         Self {
             typ: None,
             h: None,
+            day: None,
             datetime: None,
             values: Vec::new(),
         }
     }
+    /// `datetime` attribute, which BMKG publishes in UTC.
+    pub fn datetime_utc(&self) -> Option<DateTime<Utc>> {
+        let datetime = self.datetime.as_ref()?;
+        NaiveDateTime::parse_from_str(datetime, "%Y%m%d%H%M")
+            .ok()
+            .map(|d| Utc.from_utc_datetime(&d))
+    }
+    /// `day` attribute of daily time ranges, a local date.
+    pub fn local_date(&self) -> Option<NaiveDate> {
+        let day = self.day.as_ref()?;
+        NaiveDate::parse_from_str(day, "%Y%m%d").ok()
+    }
+    pub fn find_value<T: Borrow<str>>(&self, unit: T) -> Option<&Value> {
+        let unit = unit.borrow();
+        self.values.iter().find(|v| {
+            v.unit
+                .as_deref()
+                .is_some_and(|u| u.eq_ignore_ascii_case(unit))
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct Parameter {
     pub id: Option<String>,
     pub description: Option<String>,
@@ -198,6 +293,10 @@ pub struct Parameter {
     pub timeranges: Vec<TimeRange>,
 }
 
+/// Length assumed for a time range with no neighbour to measure from;
+/// BMKG publishes 6-hourly forecasts.
+const DEFAULT_STEP_HOURS: i64 = 6;
+
 impl Parameter {
     fn new() -> Self {
         Self {
@@ -207,9 +306,34 @@ impl Parameter {
             timeranges: Vec::new(),
         }
     }
+    /// End of a time range: the start of the next one of the same kind
+    /// (hourly or daily), or for the last one its start plus the previous
+    /// step. `None` without a `datetime`.
+    pub fn range_end(&self, time_range: &TimeRange) -> Option<DateTime<Utc>> {
+        let start = time_range.datetime_utc()?;
+        let daily = time_range.day.is_some();
+        let mut starts = self
+            .timeranges
+            .iter()
+            .filter(|tr| tr.day.is_some() == daily)
+            .filter_map(|tr| tr.datetime_utc())
+            .collect::<Vec<_>>();
+        starts.sort();
+        starts.dedup();
+
+        if let Some(next) = starts.iter().find(|d| **d > start) {
+            return Some(*next);
+        }
+        let step = match starts.iter().rev().find(|d| **d < start) {
+            Some(previous) => start - *previous,
+            None => chrono::Duration::hours(DEFAULT_STEP_HOURS),
+        };
+        Some(start + step)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct Name {
     pub lang: Option<String>,
     pub text: Option<String>,
@@ -225,6 +349,7 @@ impl Name {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct Area {
     pub id: Option<String>,
     pub latitude: Option<String>,
@@ -258,9 +383,150 @@ impl Area {
             parameters: Vec::new(),
         }
     }
+    /// `(latitude, longitude)` in decimal degrees.
+    pub fn position(&self) -> Option<(f64, f64)> {
+        let lat = self.latitude.as_ref()?.trim().parse().ok()?;
+        let lon = self.longitude.as_ref()?.trim().parse().ok()?;
+        Some((lat, lon))
+    }
+    pub fn distance_km(&self, latitude: f64, longitude: f64) -> Option<f64> {
+        let (lat, lon) = self.position()?;
+        Some(geo::distance_km(lat, lon, latitude, longitude))
+    }
+    pub fn parameter<T: Borrow<str>>(&self, id: T) -> Option<&Parameter> {
+        let id = id.borrow();
+        self.parameters.iter().find(|p| p.id.as_deref() == Some(id))
+    }
+    /// Time zone of the area, from its province (`domain`) or, failing
+    /// that, its longitude.
+    pub fn time_zone(&self) -> TimeZone {
+        if let Some(tz) = self.domain.as_deref().and_then(TimeZone::from_region) {
+            return tz;
+        }
+
+        match self
+            .longitude
+            .as_ref()
+            .and_then(|x| x.trim().parse::<f64>().ok())
+        {
+            Some(lon) if lon >= 127.0 => TimeZone::WIT,
+            Some(lon) if lon >= 116.0 => TimeZone::WITA,
+            _ => TimeZone::WIB,
+        }
+    }
+}
+
+/// BMKG weather codes as published in the `weather` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weather {
+    Clear,
+    PartlyCloudy,
+    MostlyCloudy,
+    Overcast,
+    Haze,
+    Smoke,
+    Fog,
+    LightRain,
+    Rain,
+    HeavyRain,
+    IsolatedShower,
+    Thunderstorm,
+}
+
+impl Weather {
+    pub fn from_code(code: u32) -> Option<Weather> {
+        match code {
+            0 | 100 => Some(Weather::Clear),
+            1 | 2 | 101 | 102 => Some(Weather::PartlyCloudy),
+            3 | 103 => Some(Weather::MostlyCloudy),
+            4 | 104 => Some(Weather::Overcast),
+            5 => Some(Weather::Haze),
+            10 => Some(Weather::Smoke),
+            45 => Some(Weather::Fog),
+            60 => Some(Weather::LightRain),
+            61 => Some(Weather::Rain),
+            63 => Some(Weather::HeavyRain),
+            80 => Some(Weather::IsolatedShower),
+            95 | 97 => Some(Weather::Thunderstorm),
+            _ => None,
+        }
+    }
+    pub fn from_value(v: &Value) -> Option<Weather> {
+        v.value
+            .as_ref()
+            .and_then(|x| x.trim().parse().ok())
+            .and_then(Weather::from_code)
+    }
+    pub fn code(&self) -> u32 {
+        match self {
+            Weather::Clear => 0,
+            Weather::PartlyCloudy => 1,
+            Weather::MostlyCloudy => 3,
+            Weather::Overcast => 4,
+            Weather::Haze => 5,
+            Weather::Smoke => 10,
+            Weather::Fog => 45,
+            Weather::LightRain => 60,
+            Weather::Rain => 61,
+            Weather::HeavyRain => 63,
+            Weather::IsolatedShower => 80,
+            Weather::Thunderstorm => 95,
+        }
+    }
+    /// Rank used to pick the worst condition, higher is worse.
+    pub fn severity(&self) -> u8 {
+        match self {
+            Weather::Clear => 0,
+            Weather::PartlyCloudy => 1,
+            Weather::MostlyCloudy => 2,
+            Weather::Overcast => 3,
+            Weather::Haze => 4,
+            Weather::Smoke => 5,
+            Weather::Fog => 6,
+            Weather::LightRain => 7,
+            Weather::IsolatedShower => 8,
+            Weather::Rain => 9,
+            Weather::HeavyRain => 10,
+            Weather::Thunderstorm => 11,
+        }
+    }
+    pub fn description_id(&self) -> &'static str {
+        match self {
+            Weather::Clear => "Cerah",
+            Weather::PartlyCloudy => "Cerah Berawan",
+            Weather::MostlyCloudy => "Berawan",
+            Weather::Overcast => "Berawan Tebal",
+            Weather::Haze => "Udara Kabur",
+            Weather::Smoke => "Asap",
+            Weather::Fog => "Kabut",
+            Weather::LightRain => "Hujan Ringan",
+            Weather::Rain => "Hujan Sedang",
+            Weather::HeavyRain => "Hujan Lebat",
+            Weather::IsolatedShower => "Hujan Lokal",
+            Weather::Thunderstorm => "Hujan Petir",
+        }
+    }
+    pub fn description_en(&self) -> &'static str {
+        match self {
+            Weather::Clear => "Clear Skies",
+            Weather::PartlyCloudy => "Partly Cloudy",
+            Weather::MostlyCloudy => "Mostly Cloudy",
+            Weather::Overcast => "Overcast",
+            Weather::Haze => "Haze",
+            Weather::Smoke => "Smoke",
+            Weather::Fog => "Fog",
+            Weather::LightRain => "Light Rain",
+            Weather::Rain => "Rain",
+            Weather::HeavyRain => "Heavy Rain",
+            Weather::IsolatedShower => "Isolated Shower",
+            Weather::Thunderstorm => "Severe Thunderstorm",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct Issue {
     pub timestamp: Option<String>,
     pub year: Option<String>,
@@ -283,9 +549,17 @@ impl Issue {
             second: None,
         }
     }
+    /// `timestamp` (`YYYYMMDDhhmmss`, UTC) as a date time.
+    pub fn datetime_utc(&self) -> Option<DateTime<Utc>> {
+        let timestamp = self.timestamp.as_ref()?;
+        NaiveDateTime::parse_from_str(timestamp, "%Y%m%d%H%M%S")
+            .ok()
+            .map(|d| Utc.from_utc_datetime(&d))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct Forecast {
     pub domain: Option<String>,
     pub issue: Option<Issue>,
@@ -303,6 +577,7 @@ impl Forecast {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct Data {
     pub source: Option<String>,
     pub production_center: Option<String>,
@@ -323,214 +598,350 @@ impl Data {
     }
 }
 
-fn parse_data<T: Borrow<str>>(xml: T) -> Result<Data, Error> {
-    let mut reader = Reader::from_str(xml.borrow());
-    reader.trim_text(true);
-
-    let mut buf = Vec::new();
-    let mut data = Data::new();
-    let mut forecast = Forecast::new();
-    let mut issue = Issue::new();
-    let mut area = Area::new();
-    let mut name = Name::new();
-    let mut parameter = Parameter::new();
-    let mut time_range = TimeRange::new();
-    let mut value = Value::new();
-
-    loop {
-        match reader.read_event(&mut buf) {
-            Ok(Event::Start(ref e)) => match e.name() {
-                b"data" => {
-                    let values = e
-                        .attributes()
-                        .map(|a| a.unwrap().unescape_and_decode_value(&reader).unwrap())
-                        .collect::<Vec<_>>();
-                    let keys = e
-                        .attributes()
-                        .map(|a| std::str::from_utf8(a.unwrap().key).unwrap())
-                        .collect::<Vec<_>>();
-                    let kvs: Vec<(_, _)> = keys.iter().zip(values.iter()).collect();
-                    for (k, v) in kvs {
-                        match k.as_ref() {
-                            "source" => data.source = Some(v.to_string()),
-                            "productioncenter" => data.production_center = Some(v.to_string()),
-                            _ => (),
+/// Capacity of the channel between the blocking parser and an [`AreaStream`].
+const AREA_STREAM_CAPACITY: usize = 8;
+
+fn attributes<B: BufRead>(
+    reader: &Reader<B>,
+    e: &BytesStart,
+) -> Result<Vec<(String, String)>, Error> {
+    e.attributes()
+        .map(|a| {
+            let a = a?;
+            let key = String::from_utf8_lossy(a.key).into_owned();
+            let value = a.unescape_and_decode_value(reader)?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// Incremental reader over a DigitalForecast document.
+///
+/// The header (`data` attributes, `forecast` domain and `issue`) is read when
+/// the reader is created. Areas are then yielded one by one as each `</area>`
+/// is reached, so only a single `Area` is held in memory at a time.
+pub struct AreaReader<B: BufRead> {
+    reader: Reader<B>,
+    buf: Vec<u8>,
+    header: Data,
+    issue: Issue,
+    area: Area,
+    parameter: Parameter,
+    time_range: TimeRange,
+    done: bool,
+}
+
+impl<B: BufRead> AreaReader<B> {
+    pub fn new(inner: B) -> Result<Self, Error> {
+        let mut reader = Reader::from_reader(inner);
+        reader.trim_text(true);
+
+        let mut area_reader = Self {
+            reader,
+            buf: Vec::new(),
+            header: Data::new(),
+            issue: Issue::new(),
+            area: Area::new(),
+            parameter: Parameter::new(),
+            time_range: TimeRange::new(),
+            done: false,
+        };
+        area_reader.read_area(true)?;
+
+        Ok(area_reader)
+    }
+
+    /// `Data` with its `Forecast` header, without any areas.
+    pub fn header(&self) -> &Data {
+        &self.header
+    }
+
+    pub fn forecast(&self) -> Option<&Forecast> {
+        self.header.forecast.as_ref()
+    }
+
+    pub fn issue(&self) -> Option<&Issue> {
+        self.forecast().and_then(|f| f.issue.as_ref())
+    }
+
+    pub fn into_header(self) -> Data {
+        self.header
+    }
+
+    /// Reads up to the next area; any error ends the iteration.
+    fn read_area(&mut self, header_only: bool) -> Result<Option<Area>, Error> {
+        let res = self.parse_area(header_only);
+        if res.is_err() {
+            self.done = true;
+        }
+        res
+    }
+
+    fn parse_area(&mut self, header_only: bool) -> Result<Option<Area>, Error> {
+        loop {
+            let event = self.reader.read_event(&mut self.buf)?;
+
+            match event {
+                Event::Start(ref e) => match e.name() {
+                    b"data" => {
+                        for (k, v) in attributes(&self.reader, e)? {
+                            match k.as_str() {
+                                "source" => self.header.source = Some(v),
+                                "productioncenter" => self.header.production_center = Some(v),
+                                _ => (),
+                            }
                         }
                     }
-                }
-                b"forecast" => {
-                    let values = e
-                        .attributes()
-                        .map(|a| a.unwrap().unescape_and_decode_value(&reader).unwrap())
-                        .collect::<Vec<_>>();
-
-                    for v in values {
-                        forecast.domain = Some(v.to_string())
-                    }
-                }
-                b"timestamp" => {
-                    let text = reader.read_text(e.name(), &mut Vec::new())?;
-                    issue.timestamp = Some(text);
-                }
-                b"year" => {
-                    let text = reader.read_text(e.name(), &mut Vec::new())?;
-                    issue.year = Some(text);
-                }
-                b"month" => {
-                    let text = reader.read_text(e.name(), &mut Vec::new())?;
-                    issue.month = Some(text);
-                }
-                b"day" => {
-                    let text = reader.read_text(e.name(), &mut Vec::new())?;
-                    issue.day = Some(text);
-                }
-                b"hour" => {
-                    let text = reader.read_text(e.name(), &mut Vec::new())?;
-                    issue.hour = Some(text);
-                }
-                b"minute" => {
-                    let text = reader.read_text(e.name(), &mut Vec::new())?;
-                    issue.minute = Some(text);
-                }
-                b"second" => {
-                    let text = reader.read_text(e.name(), &mut Vec::new())?;
-                    issue.second = Some(text);
-                }
-                b"area" => {
-                    let values = e
-                        .attributes()
-                        .map(|a| a.unwrap().unescape_and_decode_value(&reader).unwrap())
-                        .collect::<Vec<_>>();
-                    let keys = e
-                        .attributes()
-                        .map(|a| std::str::from_utf8(a.unwrap().key).unwrap())
-                        .collect::<Vec<_>>();
-                    let kvs: Vec<(_, _)> = keys.iter().zip(values.iter()).collect();
-                    for (k, v) in kvs {
-                        match k.as_ref() {
-                            "id" => area.id = Some(v.to_string()),
-                            "latitude" => area.latitude = Some(v.to_string()),
-                            "longitude" => area.longitude = Some(v.to_string()),
-                            "coordinate" => area.coordinate = Some(v.to_string()),
-                            "type" => area.typ = Some(v.to_string()),
-                            "region" => area.region = Some(v.to_string()),
-                            "level" => area.level = Some(v.to_string()),
-                            "description" => area.description = Some(v.to_string()),
-                            "domain" => area.domain = Some(v.to_string()),
-                            "tags" => area.tags = Some(v.to_string()),
-                            _ => (),
+                    b"forecast" => {
+                        let mut forecast = Forecast::new();
+                        for (_, v) in attributes(&self.reader, e)? {
+                            forecast.domain = Some(v)
                         }
+                        self.header.forecast = Some(forecast);
                     }
-                }
-                b"name" => {
-                    let text = reader.read_text(e.name(), &mut Vec::new())?;
-                    name.text = Some(text);
-                    let values = e
-                        .attributes()
-                        .map(|a| a.unwrap().unescape_and_decode_value(&reader).unwrap())
-                        .collect::<Vec<_>>();
-
-                    for v in values {
-                        name.lang = Some(v.to_string())
+                    b"timestamp" => {
+                        let text = self.reader.read_text(e.name(), &mut Vec::new())?;
+                        self.issue.timestamp = Some(text);
                     }
-
-                    area.names.push(name);
-                    name = Name::new();
-                }
-                b"parameter" => {
-                    let values = e
-                        .attributes()
-                        .map(|a| a.unwrap().unescape_and_decode_value(&reader).unwrap())
-                        .collect::<Vec<_>>();
-                    let keys = e
-                        .attributes()
-                        .map(|a| std::str::from_utf8(a.unwrap().key).unwrap())
-                        .collect::<Vec<_>>();
-                    let kvs: Vec<(_, _)> = keys.iter().zip(values.iter()).collect();
-                    for (k, v) in kvs {
-                        match k.as_ref() {
-                            "id" => parameter.id = Some(v.to_string()),
-                            "type" => parameter.typ = Some(v.to_string()),
-                            "description" => parameter.description = Some(v.to_string()),
-                            _ => (),
+                    b"year" => {
+                        let text = self.reader.read_text(e.name(), &mut Vec::new())?;
+                        self.issue.year = Some(text);
+                    }
+                    b"month" => {
+                        let text = self.reader.read_text(e.name(), &mut Vec::new())?;
+                        self.issue.month = Some(text);
+                    }
+                    b"day" => {
+                        let text = self.reader.read_text(e.name(), &mut Vec::new())?;
+                        self.issue.day = Some(text);
+                    }
+                    b"hour" => {
+                        let text = self.reader.read_text(e.name(), &mut Vec::new())?;
+                        self.issue.hour = Some(text);
+                    }
+                    b"minute" => {
+                        let text = self.reader.read_text(e.name(), &mut Vec::new())?;
+                        self.issue.minute = Some(text);
+                    }
+                    b"second" => {
+                        let text = self.reader.read_text(e.name(), &mut Vec::new())?;
+                        self.issue.second = Some(text);
+                    }
+                    b"area" => {
+                        self.area = Area::new();
+                        for (k, v) in attributes(&self.reader, e)? {
+                            match k.as_str() {
+                                "id" => self.area.id = Some(v),
+                                "latitude" => self.area.latitude = Some(v),
+                                "longitude" => self.area.longitude = Some(v),
+                                "coordinate" => self.area.coordinate = Some(v),
+                                "type" => self.area.typ = Some(v),
+                                "region" => self.area.region = Some(v),
+                                "level" => self.area.level = Some(v),
+                                "description" => self.area.description = Some(v),
+                                "domain" => self.area.domain = Some(v),
+                                "tags" => self.area.tags = Some(v),
+                                _ => (),
+                            }
+                        }
+                        if header_only {
+                            return Ok(None);
                         }
                     }
-                }
-                b"timerange" => {
-                    let values = e
-                        .attributes()
-                        .map(|a| a.unwrap().unescape_and_decode_value(&reader).unwrap())
-                        .collect::<Vec<_>>();
-                    let keys = e
-                        .attributes()
-                        .map(|a| std::str::from_utf8(a.unwrap().key).unwrap())
-                        .collect::<Vec<_>>();
-                    let kvs: Vec<(_, _)> = keys.iter().zip(values.iter()).collect();
-                    for (k, v) in kvs {
-                        match k.as_ref() {
-                            "h" => time_range.h = Some(v.to_string()),
-                            "type" => time_range.typ = Some(v.to_string()),
-                            "datetime" => time_range.datetime = Some(v.to_string()),
-                            _ => (),
+                    b"name" => {
+                        let mut name = Name::new();
+                        name.text = Some(self.reader.read_text(e.name(), &mut Vec::new())?);
+                        for (_, v) in attributes(&self.reader, e)? {
+                            name.lang = Some(v)
                         }
+
+                        self.area.names.push(name);
                     }
-                }
-                b"value" => {
-                    let text = reader.read_text(e.name(), &mut Vec::new())?;
-                    value.value = Some(text);
-                    let values = e
-                        .attributes()
-                        .map(|a| a.unwrap().unescape_and_decode_value(&reader).unwrap())
-                        .collect::<Vec<_>>();
-
-                    for v in values {
-                        value.unit = Some(v.to_string())
+                    b"parameter" => {
+                        for (k, v) in attributes(&self.reader, e)? {
+                            match k.as_str() {
+                                "id" => self.parameter.id = Some(v),
+                                "type" => self.parameter.typ = Some(v),
+                                "description" => self.parameter.description = Some(v),
+                                _ => (),
+                            }
+                        }
+                    }
+                    b"timerange" => {
+                        for (k, v) in attributes(&self.reader, e)? {
+                            match k.as_str() {
+                                "h" => self.time_range.h = Some(v),
+                                "day" => self.time_range.day = Some(v),
+                                "type" => self.time_range.typ = Some(v),
+                                "datetime" => self.time_range.datetime = Some(v),
+                                _ => (),
+                            }
+                        }
                     }
+                    b"value" => {
+                        let mut value = Value::new();
+                        value.value = Some(self.reader.read_text(e.name(), &mut Vec::new())?);
+                        for (_, v) in attributes(&self.reader, e)? {
+                            value.unit = Some(v)
+                        }
 
-                    time_range.values.push(value);
-                    value = Value::new();
-                }
-                _ => (),
-            },
-            Ok(Event::End(ref e)) => match e.name() {
-                b"data" => {}
-                b"forecast" => {
-                    data.forecast = Some(forecast.clone());
-                }
-                b"issue" => {
-                    forecast.issue = Some(issue.clone());
-                }
-                b"area" => {
-                    forecast.areas.push(area);
-                    area = Area::new();
+                        self.time_range.values.push(value);
+                    }
+                    _ => (),
+                },
+                Event::End(ref e) => match e.name() {
+                    b"issue" => {
+                        let issue = std::mem::replace(&mut self.issue, Issue::new());
+                        self.header.forecast.get_or_insert_with(Forecast::new).issue = Some(issue);
+                    }
+                    b"area" => {
+                        self.buf.clear();
+                        return Ok(Some(std::mem::replace(&mut self.area, Area::new())));
+                    }
+                    b"parameter" => {
+                        let parameter = std::mem::replace(&mut self.parameter, Parameter::new());
+                        self.area.parameters.push(parameter);
+                    }
+                    b"timerange" => {
+                        let time_range = std::mem::replace(&mut self.time_range, TimeRange::new());
+                        self.parameter.timeranges.push(time_range);
+                    }
+                    _ => (),
+                },
+                Event::Eof => {
+                    self.done = true;
+                    return Ok(None);
                 }
-                b"parameter" => {
-                    area.parameters.push(parameter);
-                    parameter = Parameter::new();
+                _ => (), // There are several other `Event`s we do not consider here
+            }
+            self.buf.clear();
+        }
+    }
+}
+
+impl<B: BufRead> Iterator for AreaReader<B> {
+    type Item = Result<Area, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        self.read_area(false).transpose()
+    }
+}
+
+/// Asynchronous counterpart of [`AreaReader`].
+///
+/// Parsing runs on a blocking task which hands areas over through a bounded
+/// channel, so memory use stays constant regardless of the document size.
+pub struct AreaStream {
+    header: Data,
+    rx: mpsc::Receiver<Result<Area, Error>>,
+}
+
+impl AreaStream {
+    pub async fn new<R>(inner: R) -> Result<Self, Error>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let bridge = SyncIoBridge::new(inner);
+        let (header_tx, header_rx) = oneshot::channel();
+        let (tx, rx) = mpsc::channel(AREA_STREAM_CAPACITY);
+
+        tokio::task::spawn_blocking(move || {
+            let reader = match AreaReader::new(io::BufReader::new(bridge)) {
+                Ok(reader) => reader,
+                Err(e) => {
+                    let _ = header_tx.send(Err(e));
+                    return;
                 }
-                b"timerange" => {
-                    parameter.timeranges.push(time_range);
-                    time_range = TimeRange::new();
+            };
+            if header_tx.send(Ok(reader.header().clone())).is_err() {
+                return;
+            }
+            for area in reader {
+                if tx.blocking_send(area).is_err() {
+                    break;
                 }
-                _ => (),
-            },
-            Ok(Event::Eof) => break, // exits the loop when reaching end of file
-            Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            _ => (), // There are several other `Event`s we do not consider here
-        }
-        buf.clear();
+            }
+        });
+
+        let header = header_rx.await.map_err(|_| {
+            Error::Others("forecast parser stopped before the header".to_string())
+        })??;
+
+        Ok(Self { header, rx })
+    }
+
+    /// `Data` with its `Forecast` header, without any areas.
+    pub fn header(&self) -> &Data {
+        &self.header
+    }
+
+    pub fn forecast(&self) -> Option<&Forecast> {
+        self.header.forecast.as_ref()
+    }
+
+    pub fn issue(&self) -> Option<&Issue> {
+        self.forecast().and_then(|f| f.issue.as_ref())
+    }
+
+    pub async fn next(&mut self) -> Option<Result<Area, Error>> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for AreaStream {
+    type Item = Result<Area, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+fn parse_data<B: BufRead>(inner: B) -> Result<Data, Error> {
+    let mut reader = AreaReader::new(inner)?;
+    let areas = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+
+    let mut data = reader.into_header();
+    if let Some(ref mut forecast) = data.forecast {
+        forecast.areas = areas;
     }
 
-    // println!("data {:#?}", data);
     Ok(data)
 }
 
+/// Parses a DigitalForecast document held in memory, e.g. a `&str` or `Vec<u8>`.
+pub fn parse_forecast<T: AsRef<[u8]>>(xml: T) -> Result<Data, Error> {
+    parse_data(xml.as_ref())
+}
+
+pub fn parse_forecast_reader<R: Read>(r: R) -> Result<Data, Error> {
+    parse_data(io::BufReader::new(r))
+}
+
+pub fn parse_forecast_file<P: AsRef<Path>>(path: P) -> Result<Data, Error> {
+    let file = File::open(path)?;
+    parse_forecast_reader(file)
+}
+
 pub async fn get_data(p: Province) -> Result<Data, Error> {
-    let xml = reqwest::get(&p.to_url()).await?.text().await?;
-    let data = parse_data(xml)?;
+    let xml = reqwest::get(&p.to_url()).await?.bytes().await?;
+    let data = parse_forecast(xml)?;
     Ok(data)
 }
 
+/// Fetches a province and parses its areas as they arrive, see [`AreaStream`].
+pub async fn stream_data(p: Province) -> Result<AreaStream, Error> {
+    let body = reqwest::get(&p.to_url())
+        .await?
+        .bytes_stream()
+        .map_err(io::Error::other);
+
+    AreaStream::new(StreamReader::new(Box::pin(body))).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -542,6 +953,175 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    const SAMPLE: &str = include_str!("../fixtures/DigitalForecast-Sample.xml");
+
+    #[test]
+    fn parse_data_test() {
+        let data = parse_forecast(SAMPLE).unwrap();
+        let forecast = data.forecast.unwrap();
+
+        assert_eq!(data.source, Some("meteo".to_string()));
+        assert_eq!(forecast.domain, Some("local".to_string()));
+        assert_eq!(forecast.areas.len(), 2);
+        assert_eq!(forecast.areas[0].names.len(), 2);
+        assert_eq!(forecast.areas[0].parameters.len(), 9);
+        assert_eq!(forecast.areas[0].parameters[0].timeranges.len(), 8);
+    }
+
+    #[test]
+    fn parse_forecast_bytes_test() {
+        let from_str = parse_forecast(SAMPLE).unwrap();
+        let from_bytes = parse_forecast(SAMPLE.as_bytes()).unwrap();
+        let from_reader = parse_forecast_reader(SAMPLE.as_bytes()).unwrap();
+
+        assert_eq!(from_str.to_json(), from_bytes.to_json());
+        assert_eq!(
+            from_reader.to_json(),
+            parse_forecast(SAMPLE).unwrap().to_json()
+        );
+    }
+
+    #[test]
+    fn parse_forecast_file_test() {
+        let data = parse_forecast_file("fixtures/DigitalForecast-Sample.xml").unwrap();
+
+        assert_eq!(data.forecast.unwrap().areas.len(), 2);
+    }
+
+    #[test]
+    fn parse_forecast_file_not_found_test() {
+        let res = parse_forecast_file("fixtures/does-not-exist.xml");
+
+        assert!(matches!(res, Err(Error::IoError(_))));
+    }
+
+    #[test]
+    fn area_reader_header_test() {
+        let reader = AreaReader::new(SAMPLE.as_bytes()).unwrap();
+        let issue = reader.issue().unwrap();
+
+        assert_eq!(issue.timestamp, Some("20211019041211".to_string()));
+        assert_eq!(
+            reader.header().production_center,
+            Some("jakarta".to_string())
+        );
+        assert!(reader.forecast().unwrap().areas.is_empty());
+    }
+
+    #[test]
+    fn area_reader_iter_test() {
+        let reader = AreaReader::new(SAMPLE.as_bytes()).unwrap();
+        let areas = reader.collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(areas.len(), 2);
+        assert_eq!(areas[0].id, Some("501195".to_string()));
+        assert_eq!(areas[1].description, Some("Kepulauan Seribu".to_string()));
+        assert_eq!(areas[1].parameters.len(), 9);
+    }
+
+    #[test]
+    fn area_reader_error_test() {
+        let data = "<data><forecast><area id=\"1\"></forecast></data>";
+        let mut reader = AreaReader::new(data.as_bytes()).unwrap();
+
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn area_reader_attribute_error_test() {
+        let data = "<data><forecast><area id=\"1\"></area><area id=2></area><area id=\"3\"></area></forecast></data>";
+        let mut reader = AreaReader::new(data.as_bytes()).unwrap();
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[tokio::test]
+    async fn area_stream_test() {
+        let mut stream = AreaStream::new(SAMPLE.as_bytes()).await.unwrap();
+        let mut ids = Vec::new();
+        while let Some(area) = stream.next().await {
+            ids.push(area.unwrap().id.unwrap());
+        }
+
+        assert_eq!(stream.issue().unwrap().year, Some("2021".to_string()));
+        assert_eq!(ids, vec!["501195", "5002220"]);
+    }
+
+    #[test]
+    fn province_all_test() {
+        let all = Province::all();
+
+        assert_eq!(all.len(), Domain::get_data().len());
+        assert_eq!(all[6], Province::from_str("dki").unwrap());
+    }
+
+    #[test]
+    fn province_key_test() {
+        for p in Province::all() {
+            assert_eq!(Province::from_str(p.key()).map(|x| x.key()), Some(p.key()));
+        }
+    }
+
+    #[test]
+    fn province_from_str_sumatera_barat_test() {
+        assert_eq!(
+            Province::from_str("sumatera_barat"),
+            Some(Province::SumateraBarat)
+        );
+        assert_ne!(
+            Province::from_str("sumatera_barat"),
+            Province::from_str("sumatera_utara")
+        );
+    }
+
+    #[test]
+    fn issue_datetime_test() {
+        let data = parse_forecast(SAMPLE).unwrap();
+        let issue = data.forecast.unwrap().issue.unwrap();
+
+        assert_eq!(
+            issue.datetime_utc().unwrap().to_rfc3339(),
+            "2021-10-19T04:12:11+00:00"
+        );
+    }
+
+    #[test]
+    fn area_position_test() {
+        let data = parse_forecast(SAMPLE).unwrap();
+        let area = &data.forecast.unwrap().areas[0];
+
+        assert_eq!(area.position(), Some((-6.1819, 106.8294)));
+        assert!(area.distance_km(-6.1819, 106.8294).unwrap() < 1e-9);
+    }
+
+    #[test]
+    fn weather_from_code_test() {
+        let heavy = Weather::from_code(63).unwrap();
+
+        assert_eq!(Weather::from_code(97), Some(Weather::Thunderstorm));
+        assert_eq!(Weather::from_code(7), None);
+        assert_eq!(heavy.code(), 63);
+        assert!(heavy.severity() > Weather::Rain.severity());
+    }
+
+    #[test]
+    fn time_range_helpers_test() {
+        let data = parse_forecast(SAMPLE).unwrap();
+        let area = &data.forecast.unwrap().areas[0];
+        let tmax = &area.parameter("tmax").unwrap().timeranges[0];
+        let t = &area.parameter("t").unwrap().timeranges[1];
+
+        assert_eq!(tmax.local_date(), NaiveDate::from_ymd_opt(2021, 10, 19));
+        assert_eq!(tmax.find_value("f").unwrap().as_f64(), Some(91.4));
+        assert_eq!(
+            t.datetime_utc().unwrap().to_rfc3339(),
+            "2021-10-19T06:00:00+00:00"
+        );
+    }
+
     #[test]
     fn domain_list_get_data_test() {
         let data = Domain::get_data();
@@ -605,7 +1185,7 @@ mod tests {
             Province::SulawesiUtara,
             Province::Indonesia,
         ];
-        let result: Vec<String> = data.iter().map(|i| Province::to_url(i)).collect();
+        let result: Vec<String> = data.iter().map(Province::to_url).collect();
 
         assert!(result.len() == 35);
     }
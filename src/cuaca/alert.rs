@@ -0,0 +1,355 @@
+use crate::cuaca::units::{Quantity, Unit};
+use crate::cuaca::{Area, Data, TimeRange, Weather};
+use crate::{read_config, Error};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operator {
+    #[serde(rename = ">", alias = "gt")]
+    Gt,
+    #[serde(rename = ">=", alias = "ge")]
+    Ge,
+    #[serde(rename = "<", alias = "lt")]
+    Lt,
+    #[serde(rename = "<=", alias = "le")]
+    Le,
+    #[serde(rename = "==", alias = "eq")]
+    Eq,
+    #[serde(rename = "!=", alias = "ne")]
+    Ne,
+}
+
+impl Operator {
+    pub fn apply(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Operator::Gt => value > threshold,
+            Operator::Ge => value >= threshold,
+            Operator::Lt => value < threshold,
+            Operator::Le => value <= threshold,
+            Operator::Eq => (value - threshold).abs() < f64::EPSILON,
+            Operator::Ne => (value - threshold).abs() >= f64::EPSILON,
+        }
+    }
+}
+
+/// A condition on one forecast parameter.
+///
+/// For the `weather` parameter values and threshold are weather codes and
+/// are compared by [`Weather::severity`], so `>= 63` matches heavy rain and
+/// thunderstorms but not isolated showers (80).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: String,
+    pub description: Option<String>,
+    /// Parameter id, e.g. `weather`, `t`, `tmax`, `hu` or `ws`.
+    pub parameter: String,
+    pub op: Operator,
+    pub threshold: f64,
+    /// Unit of `threshold` as spelled by BMKG (`C`, `Kt`, ...). Values are
+    /// converted to it when needed; without a unit the first value is used.
+    pub unit: Option<String>,
+    /// Only time ranges overlapping the next this many hours from the evaluation time.
+    pub within_hours: Option<i64>,
+    /// Area ids the rule applies to, all areas when empty.
+    #[serde(default)]
+    pub areas: Vec<String>,
+}
+
+impl Rule {
+    fn value(&self, time_range: &TimeRange) -> Option<(f64, Option<String>)> {
+        match self.unit.as_deref() {
+            Some(symbol) => {
+                let unit = Unit::from_str(symbol)?;
+                let quantities = time_range
+                    .values
+                    .iter()
+                    .filter_map(Quantity::from_value)
+                    .collect::<Vec<_>>();
+                let value = quantities
+                    .iter()
+                    .find(|q| q.unit == unit)
+                    .cloned()
+                    .or_else(|| quantities.iter().find_map(|q| q.to(unit)))?;
+                Some((value.value, Some(unit.symbol().to_string())))
+            }
+            None => {
+                let value = time_range.values.first()?;
+                Some((value.as_f64()?, value.unit.clone()))
+            }
+        }
+    }
+
+    fn matches(&self, value: f64) -> bool {
+        if self.parameter == "weather" {
+            let rank = |code: f64| Weather::from_code(code as u32).map(|w| w.severity() as f64);
+            match (rank(value), rank(self.threshold)) {
+                (Some(v), Some(t)) => self.op.apply(v, t),
+                _ => false,
+            }
+        } else {
+            self.op.apply(value, self.threshold)
+        }
+    }
+
+    fn applies_to(&self, area: &Area) -> bool {
+        self.areas.is_empty()
+            || area
+                .id
+                .as_ref()
+                .is_some_and(|id| self.areas.iter().any(|x| x == id))
+    }
+
+    pub fn evaluate_area(&self, area: &Area, now: DateTime<Utc>) -> Vec<Alert> {
+        if !self.applies_to(area) {
+            return Vec::new();
+        }
+        let parameter = match area.parameter(self.parameter.as_str()) {
+            Some(p) => p,
+            None => return Vec::new(),
+        };
+        let until = self.within_hours.map(|h| now + Duration::hours(h));
+
+        parameter
+            .timeranges
+            .iter()
+            .filter(
+                |t| match (until, t.datetime_utc(), parameter.range_end(t)) {
+                    // ranges overlapping [now, until], including the one in effect
+                    (Some(until), Some(start), Some(end)) => end > now && start <= until,
+                    (Some(_), _, _) => false,
+                    (None, _, _) => true,
+                },
+            )
+            .filter_map(|t| {
+                let (value, unit) = self.value(t)?;
+                if !self.matches(value) {
+                    return None;
+                }
+                Some(Alert {
+                    rule_id: self.id.clone(),
+                    area_id: area.id.clone(),
+                    area_name: area.description.clone(),
+                    parameter: self.parameter.clone(),
+                    datetime: t.datetime_utc(),
+                    h: t.h.clone(),
+                    value,
+                    unit,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub rule_id: String,
+    pub area_id: Option<String>,
+    pub area_name: Option<String>,
+    pub parameter: String,
+    /// Start of the triggering time range.
+    pub datetime: Option<DateTime<Utc>>,
+    pub h: Option<String>,
+    pub value: f64,
+    pub unit: Option<String>,
+}
+
+impl Alert {
+    pub fn to_json(self) -> JsonValue {
+        json!(self)
+    }
+}
+
+/// Rules as loaded from a `[[rules]]` TOML table array or a `{"rules": [...]}` JSON document.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn from_toml(s: &str) -> Result<Self, Error> {
+        let rules: Self = toml::from_str(s)?;
+        rules.validate()
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, Error> {
+        let rules: Self = serde_json::from_str(s)?;
+        rules.validate()
+    }
+
+    /// Loads a `.toml` or `.json` file, chosen by extension.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let rules: Self = read_config(path)?;
+        rules.validate()
+    }
+
+    /// Rejects `weather` rules whose threshold is not a known weather code,
+    /// which would otherwise never fire.
+    fn validate(self) -> Result<Self, Error> {
+        for rule in self.rules.iter().filter(|r| r.parameter == "weather") {
+            let t = rule.threshold;
+            let known = t.fract() == 0.0 && t >= 0.0 && Weather::from_code(t as u32).is_some();
+            if !known {
+                let msg = format!("rule {}: unknown weather code {}", rule.id, t);
+                return Err(Error::Others(msg));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Evaluates every rule against every area. Look-ahead windows start at `now`.
+    pub fn evaluate(&self, data: &Data, now: DateTime<Utc>) -> Vec<Alert> {
+        let areas = match data.forecast {
+            Some(ref f) => f.areas.as_slice(),
+            None => return Vec::new(),
+        };
+
+        areas
+            .iter()
+            .flat_map(|area| {
+                self.rules
+                    .iter()
+                    .flat_map(move |r| r.evaluate_area(area, now))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast;
+    use chrono::TimeZone;
+
+    const SAMPLE: &str = include_str!("../../fixtures/DigitalForecast-Sample.xml");
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 10, 19, 0, 0, 0).unwrap()
+    }
+
+    fn rule_set() -> RuleSet {
+        RuleSet::from_file("fixtures/forecast_rules.toml").unwrap()
+    }
+
+    #[test]
+    fn rule_set_from_toml_test() {
+        let rules = rule_set();
+
+        assert_eq!(rules.rules.len(), 3);
+        assert_eq!(rules.rules[0].op, Operator::Ge);
+        assert_eq!(rules.rules[0].threshold, 63.0);
+        assert_eq!(rules.rules[2].areas, vec!["501195".to_string()]);
+    }
+
+    #[test]
+    fn rule_set_from_json_test() {
+        let json =
+            r#"{"rules": [{"id": "humid", "parameter": "hu", "op": "gt", "threshold": 89}]}"#;
+        let rules = RuleSet::from_json(json).unwrap();
+        let data = parse_forecast(SAMPLE).unwrap();
+        let alerts = rules.evaluate(&data, now());
+
+        assert_eq!(alerts.len(), 2);
+        assert!(alerts.iter().all(|a| a.value == 90.0));
+    }
+
+    #[test]
+    fn rule_set_unknown_weather_code_test() {
+        let rule = |threshold: &str| {
+            format!(
+                r#"{{"rules": [{{"id": "w", "parameter": "weather", "op": ">=", "threshold": {}}}]}}"#,
+                threshold
+            )
+        };
+
+        assert!(RuleSet::from_json(&rule("63")).is_ok());
+        assert!(RuleSet::from_json(&rule("64")).is_err());
+        assert!(RuleSet::from_json(&rule("63.5")).is_err());
+        assert!(RuleSet::from_toml(
+            "[[rules]]\nid = \"w\"\nparameter = \"weather\"\nop = \">=\"\nthreshold = 7"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rule_set_unsupported_file_test() {
+        assert!(RuleSet::from_file("fixtures/autogempa.xml").is_err());
+    }
+
+    #[test]
+    fn evaluate_weather_window_test() {
+        let data = parse_forecast(SAMPLE).unwrap();
+        let alerts = rule_set().evaluate(&data, now());
+        let rain = alerts
+            .iter()
+            .filter(|a| a.rule_id == "heavy-rain-24h")
+            .collect::<Vec<_>>();
+
+        // 63 at h=30 and 95 at h=36 are beyond 24 hours
+        assert!(rain.is_empty());
+
+        let later = rule_set().evaluate(&data, now() + Duration::hours(12));
+        let rain = later
+            .iter()
+            .filter(|a| a.rule_id == "heavy-rain-24h")
+            .map(|a| a.value)
+            .collect::<Vec<_>>();
+
+        assert_eq!(rain, vec![63.0, 95.0]);
+    }
+
+    #[test]
+    fn evaluate_range_in_effect_test() {
+        let data = parse_forecast(SAMPLE).unwrap();
+        // 2021-10-20 08:00, inside the h=30 range (06:00–12:00) with code 63
+        let now = now() + Duration::hours(32);
+        let rain = rule_set()
+            .evaluate(&data, now)
+            .into_iter()
+            .filter(|a| a.rule_id == "heavy-rain-24h")
+            .map(|a| a.h.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(rain[0], "30");
+    }
+
+    #[test]
+    fn evaluate_wind_and_tmax_test() {
+        let data = parse_forecast(SAMPLE).unwrap();
+        let alerts = rule_set().evaluate(&data, now());
+        let wind = alerts.iter().find(|a| a.rule_id == "strong-wind").unwrap();
+        let hot = alerts
+            .iter()
+            .filter(|a| a.rule_id == "hot-day")
+            .collect::<Vec<_>>();
+
+        assert_eq!(wind.area_name, Some("Jakarta Pusat".to_string()));
+        assert_eq!(wind.value, 28.0);
+        assert_eq!(wind.h, Some("36".to_string()));
+        assert_eq!(hot.len(), 1);
+        assert_eq!(hot[0].value, 34.0);
+        assert_eq!(hot[0].unit, Some("C".to_string()));
+    }
+
+    #[test]
+    fn evaluate_with_conversion_test() {
+        let rule = Rule {
+            id: "hot-f".to_string(),
+            description: None,
+            parameter: "tmax".to_string(),
+            op: Operator::Ge,
+            threshold: 93.0,
+            unit: Some("F".to_string()),
+            within_hours: None,
+            areas: Vec::new(),
+        };
+        let data = parse_forecast(SAMPLE).unwrap();
+        let area = &data.forecast.unwrap().areas[0];
+        let alerts = rule.evaluate_area(area, now());
+
+        assert_eq!(alerts.len(), 1);
+        assert!((alerts[0].value - 93.2).abs() < 1e-9);
+    }
+}
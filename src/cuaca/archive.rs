@@ -0,0 +1,513 @@
+//! Forecast archive with time-travel queries, enabled by the `sqlite` feature.
+//!
+//! Every issuance is recorded under its province and `Issue::timestamp`.
+//! Issuances whose areas are identical to an already stored one share the
+//! stored document, so frequent reissues cost one row each.
+
+use crate::cuaca::{Area, Data, Issue, Province, Value};
+use crate::{sql_timestamp, Error};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS forecast_content (
+    hash TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS forecast_issuance (
+    province TEXT NOT NULL,
+    issued INTEGER NOT NULL,
+    issue TEXT NOT NULL,
+    hash TEXT NOT NULL REFERENCES forecast_content (hash),
+    archived_at INTEGER NOT NULL,
+    PRIMARY KEY (province, issued)
+);
+";
+
+/// Outcome of [`Archive::store`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stored {
+    /// A new issuance with new content.
+    New,
+    /// A new issuance whose content was already stored.
+    Duplicate,
+    /// This issuance was archived before; nothing changed.
+    AlreadyArchived,
+}
+
+/// What to remove in [`Archive::prune`]; an issuance goes when either limit says so.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Retention {
+    /// Remove issuances issued longer ago than this many days.
+    pub max_age_days: Option<i64>,
+    /// Keep at most this many of the latest issuances per province.
+    pub keep_last: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issuance {
+    pub issued: DateTime<Utc>,
+    pub data: Data,
+}
+
+/// Values of one parameter in effect at the requested time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterValues {
+    pub id: Option<String>,
+    pub description: Option<String>,
+    /// Start of the hourly time range, `None` for daily ones.
+    pub datetime: Option<DateTime<Utc>>,
+    /// Local date of the daily time range, `None` for hourly ones.
+    pub day: Option<NaiveDate>,
+    pub values: Vec<Value>,
+}
+
+/// What an issuance said for one area at one time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub issued: DateTime<Utc>,
+    pub area_id: Option<String>,
+    pub area_name: Option<String>,
+    pub at: DateTime<Utc>,
+    pub parameters: Vec<ParameterValues>,
+}
+
+impl Snapshot {
+    fn new(issued: DateTime<Utc>, area: &Area, at: DateTime<Utc>) -> Self {
+        let date = at
+            .with_timezone(&area.time_zone().utc_offset())
+            .date_naive();
+        let parameters = area
+            .parameters
+            .iter()
+            .filter_map(|p| {
+                let hourly = p
+                    .timeranges
+                    .iter()
+                    .filter(|tr| tr.day.is_none())
+                    .filter_map(|tr| Some((tr.datetime_utc()?, p.range_end(tr)?, tr)))
+                    .filter(|(start, end, _)| *start <= at && at < *end)
+                    .map(|(start, _, tr)| (start, tr))
+                    .max_by_key(|(d, _)| *d);
+                let (datetime, day, tr) = match hourly {
+                    Some((d, tr)) => (Some(d), None, tr),
+                    None => {
+                        let tr = p
+                            .timeranges
+                            .iter()
+                            .find(|tr| tr.local_date() == Some(date))?;
+                        (None, Some(date), tr)
+                    }
+                };
+                Some(ParameterValues {
+                    id: p.id.clone(),
+                    description: p.description.clone(),
+                    datetime,
+                    day,
+                    values: tr.values.clone(),
+                })
+            })
+            .collect();
+
+        Self {
+            issued,
+            area_id: area.id.clone(),
+            area_name: area.description.clone(),
+            at,
+            parameters,
+        }
+    }
+
+    pub fn parameter(&self, id: &str) -> Option<&ParameterValues> {
+        self.parameters.iter().find(|p| p.id.as_deref() == Some(id))
+    }
+}
+
+pub struct Archive {
+    conn: Connection,
+}
+
+/// Hash of everything but the issue, so reissues of the same forecast match.
+fn content_hash(data: &Data) -> Result<String, Error> {
+    let forecast = data.forecast.as_ref();
+    let content = serde_json::to_vec(&(
+        &data.source,
+        &data.production_center,
+        forecast.map(|f| &f.domain),
+        forecast.map(|f| &f.areas),
+    ))?;
+    Ok(hex::encode(Sha256::digest(content)))
+}
+
+impl Archive {
+    /// Opens or creates the archive database at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::init(Connection::open(path)?)
+    }
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::init(Connection::open_in_memory()?)
+    }
+    fn init(conn: Connection) -> Result<Self, Error> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Archives `data`, which must carry an issue timestamp.
+    pub fn store(&mut self, p: Province, data: &Data, now: DateTime<Utc>) -> Result<Stored, Error> {
+        let issue = data
+            .forecast
+            .as_ref()
+            .and_then(|f| f.issue.as_ref())
+            .ok_or_else(|| Error::Others("forecast without issue".to_string()))?;
+        let issued = issue
+            .datetime_utc()
+            .ok_or_else(|| Error::Others("forecast without issue timestamp".to_string()))?;
+
+        let tx = self.conn.transaction()?;
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM forecast_issuance WHERE province = ?1 AND issued = ?2",
+                params![p.key(), issued.timestamp()],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if exists {
+            return Ok(Stored::AlreadyArchived);
+        }
+
+        let hash = content_hash(data)?;
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO forecast_content (hash, data) VALUES (?1, ?2)",
+            params![hash, serde_json::to_string(data)?],
+        )?;
+        tx.execute(
+            "INSERT INTO forecast_issuance (province, issued, issue, hash, archived_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                p.key(),
+                issued.timestamp(),
+                serde_json::to_string(issue)?,
+                hash,
+                now.timestamp()
+            ],
+        )?;
+        tx.commit()?;
+
+        Ok(if inserted > 0 {
+            Stored::New
+        } else {
+            Stored::Duplicate
+        })
+    }
+
+    /// Issue times archived for `p`, oldest first.
+    pub fn issuances(&self, p: Province) -> Result<Vec<DateTime<Utc>>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT issued FROM forecast_issuance WHERE province = ?1 ORDER BY issued")?;
+        let rows = stmt.query_map(params![p.key()], |row| sql_timestamp(row, 0))?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// The latest issuance for `p` issued at or before `t`, i.e. the forecast
+    /// that was current at `t`.
+    pub fn issued_at(&self, p: Province, t: DateTime<Utc>) -> Result<Option<Issuance>, Error> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT i.issued, i.issue, c.data
+                 FROM forecast_issuance i JOIN forecast_content c ON c.hash = i.hash
+                 WHERE i.province = ?1 AND i.issued <= ?2
+                 ORDER BY i.issued DESC LIMIT 1",
+                params![p.key(), t.timestamp()],
+                |row| {
+                    Ok((
+                        sql_timestamp(row, 0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        match row {
+            Some((issued, issue, data)) => {
+                let issue: Issue = serde_json::from_str(&issue)?;
+                let mut data: Data = serde_json::from_str(&data)?;
+                if let Some(ref mut f) = data.forecast {
+                    f.issue = Some(issue);
+                }
+                Ok(Some(Issuance { issued, data }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// What the forecast for `p` current at `issued_at` said for area
+    /// `area_id` at `at`: the hourly time range starting at or before `at`
+    /// and the daily time range of its local date.
+    pub fn lookup(
+        &self,
+        p: Province,
+        issued_at: DateTime<Utc>,
+        area_id: &str,
+        at: DateTime<Utc>,
+    ) -> Result<Option<Snapshot>, Error> {
+        let issuance = match self.issued_at(p, issued_at)? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let area = issuance
+            .data
+            .forecast
+            .as_ref()
+            .and_then(|f| f.areas.iter().find(|a| a.id.as_deref() == Some(area_id)));
+
+        Ok(area.map(|a| Snapshot::new(issuance.issued, a, at)))
+    }
+
+    /// Applies `retention` to every province in one transaction, returning
+    /// the number of issuances removed. Content no longer referenced is
+    /// dropped too.
+    pub fn prune(&mut self, retention: &Retention, now: DateTime<Utc>) -> Result<usize, Error> {
+        let tx = self.conn.transaction()?;
+        let mut removed = 0;
+        if let Some(days) = retention.max_age_days {
+            let cutoff = now - Duration::days(days);
+            removed += tx.execute(
+                "DELETE FROM forecast_issuance WHERE issued < ?1",
+                params![cutoff.timestamp()],
+            )?;
+        }
+        if let Some(keep) = retention.keep_last {
+            removed += tx.execute(
+                "DELETE FROM forecast_issuance WHERE rowid IN (
+                     SELECT rowid FROM (
+                         SELECT rowid, ROW_NUMBER() OVER (
+                             PARTITION BY province ORDER BY issued DESC
+                         ) AS n FROM forecast_issuance
+                     ) WHERE n > ?1
+                 )",
+                params![keep as i64],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM forecast_content
+             WHERE hash NOT IN (SELECT hash FROM forecast_issuance)",
+            [],
+        )?;
+        tx.commit()?;
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast_file;
+    use chrono::TimeZone;
+
+    fn utc(d: u32, h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 10, d, h, m, 0).unwrap()
+    }
+
+    fn reissue(data: &Data, timestamp: &str) -> Data {
+        let mut data = data.clone();
+        let forecast = data.forecast.as_mut().unwrap();
+        forecast.issue.as_mut().unwrap().timestamp = Some(timestamp.to_string());
+        data
+    }
+
+    fn archive() -> Archive {
+        let mut archive = Archive::in_memory().unwrap();
+        let first = parse_forecast_file("fixtures/DigitalForecast-Sample.xml").unwrap();
+        let same = reissue(&first, "20211019070000");
+        let mut changed = reissue(&first, "20211019100000");
+        let t = &mut changed.forecast.as_mut().unwrap().areas[0].parameters[5];
+        assert_eq!(t.id.as_deref(), Some("t"));
+        t.timeranges[1].values[0].value = Some("28".to_string());
+
+        let now = utc(19, 12, 0);
+        assert_eq!(
+            archive.store(Province::DKI, &first, now).unwrap(),
+            Stored::New
+        );
+        assert_eq!(
+            archive.store(Province::DKI, &first, now).unwrap(),
+            Stored::AlreadyArchived
+        );
+        assert_eq!(
+            archive.store(Province::DKI, &same, now).unwrap(),
+            Stored::Duplicate
+        );
+        assert_eq!(
+            archive.store(Province::DKI, &changed, now).unwrap(),
+            Stored::New
+        );
+        archive
+    }
+
+    fn content_count(archive: &Archive) -> i64 {
+        archive
+            .conn
+            .query_row("SELECT COUNT(*) FROM forecast_content", [], |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn store_dedup_test() {
+        let archive = archive();
+
+        assert_eq!(
+            archive.issuances(Province::DKI).unwrap(),
+            vec![
+                utc(19, 4, 12) + Duration::seconds(11),
+                utc(19, 7, 0),
+                utc(19, 10, 0)
+            ]
+        );
+        assert!(archive.issuances(Province::Bali).unwrap().is_empty());
+        assert_eq!(content_count(&archive), 2);
+
+        let issuance = archive
+            .issued_at(Province::DKI, utc(19, 8, 0))
+            .unwrap()
+            .unwrap();
+        let issue = issuance.data.forecast.unwrap().issue.unwrap();
+        assert_eq!(issue.timestamp.as_deref(), Some("20211019070000"));
+    }
+
+    #[test]
+    fn lookup_test() {
+        let archive = archive();
+        let at = utc(19, 7, 0);
+        let value =
+            |s: &Snapshot, id: &str| s.parameter(id).unwrap().values[0].value.clone().unwrap();
+
+        let before = archive
+            .lookup(Province::DKI, utc(19, 9, 0), "501195", at)
+            .unwrap()
+            .unwrap();
+        assert_eq!(before.issued, utc(19, 7, 0));
+        assert_eq!(value(&before, "t"), "31");
+        assert_eq!(before.parameter("t").unwrap().datetime, Some(utc(19, 6, 0)));
+        assert_eq!(value(&before, "tmax"), "33");
+        assert_eq!(
+            before.parameter("tmax").unwrap().day,
+            NaiveDate::from_ymd_opt(2021, 10, 19)
+        );
+
+        let after = archive
+            .lookup(Province::DKI, utc(19, 10, 30), "501195", at)
+            .unwrap()
+            .unwrap();
+        assert_eq!(value(&after, "t"), "28");
+
+        assert!(archive
+            .lookup(Province::DKI, utc(19, 4, 0), "501195", at)
+            .unwrap()
+            .is_none());
+        assert!(archive
+            .lookup(Province::DKI, utc(19, 9, 0), "0", at)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn lookup_beyond_horizon_test() {
+        let archive = archive();
+        let snapshot = |at| {
+            archive
+                .lookup(Province::DKI, utc(19, 9, 0), "501195", at)
+                .unwrap()
+                .unwrap()
+        };
+
+        // the last hourly range starts 2021-10-20 18:00 and lasts 6 hours
+        let last = snapshot(utc(20, 23, 0));
+        assert_eq!(last.parameter("t").unwrap().datetime, Some(utc(20, 18, 0)));
+
+        let beyond = snapshot(utc(22, 12, 0));
+        assert!(beyond.parameter("t").is_none());
+        assert!(beyond.parameter("tmax").is_none());
+    }
+
+    #[test]
+    fn store_is_atomic_test() {
+        let mut archive = Archive::in_memory().unwrap();
+        archive
+            .conn
+            .execute_batch(
+                "CREATE TRIGGER reject BEFORE INSERT ON forecast_issuance
+                 BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+            )
+            .unwrap();
+        let data = parse_forecast_file("fixtures/DigitalForecast-Sample.xml").unwrap();
+
+        assert!(archive.store(Province::DKI, &data, utc(19, 12, 0)).is_err());
+        assert_eq!(content_count(&archive), 0);
+    }
+
+    #[test]
+    fn prune_test() {
+        let mut archive = archive();
+        let keep_last = Retention {
+            keep_last: Some(1),
+            ..Retention::default()
+        };
+        assert_eq!(archive.prune(&keep_last, utc(19, 12, 0)).unwrap(), 2);
+        assert_eq!(
+            archive.issuances(Province::DKI).unwrap(),
+            vec![utc(19, 10, 0)]
+        );
+        assert_eq!(content_count(&archive), 1);
+
+        let max_age = Retention {
+            max_age_days: Some(1),
+            ..Retention::default()
+        };
+        assert_eq!(archive.prune(&max_age, utc(21, 0, 0)).unwrap(), 1);
+        assert_eq!(content_count(&archive), 0);
+    }
+
+    #[test]
+    fn prune_is_atomic_test() {
+        let mut archive = archive();
+        archive
+            .conn
+            .execute_batch(
+                "CREATE TRIGGER reject BEFORE DELETE ON forecast_content
+                 BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+            )
+            .unwrap();
+        let keep_last = Retention {
+            keep_last: Some(1),
+            ..Retention::default()
+        };
+
+        assert!(archive.prune(&keep_last, utc(19, 12, 0)).is_err());
+        assert_eq!(archive.issuances(Province::DKI).unwrap().len(), 3);
+        assert_eq!(content_count(&archive), 2);
+    }
+
+    #[test]
+    fn corrupt_timestamp_test() {
+        let archive = archive();
+        archive
+            .conn
+            .execute(
+                "UPDATE forecast_issuance SET issued = ?1 WHERE issued = ?2",
+                params![i64::MAX, utc(19, 7, 0).timestamp()],
+            )
+            .unwrap();
+
+        assert!(archive.issuances(Province::DKI).is_err());
+        assert!(archive.issuances(Province::Bali).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+//! Arrow and Parquet export of forecasts, enabled by the `arrow` feature.
+//!
+//! The layout follows [`csv`](super::csv): one row per area and time range
+//! and one column per parameter and unit, here typed. Numeric values are
+//! `Float64`, weather codes `UInt16` with a dictionary-encoded description,
+//! and cardinal wind directions dictionary-encoded strings.
+
+use crate::cuaca::csv::{rows, value, value_columns};
+use crate::cuaca::{Data, Weather};
+use crate::Error;
+use ::arrow::array::{
+    ArrayRef, Date32Array, DictionaryArray, Float64Array, Int32Array, StringArray,
+    TimestampSecondArray, UInt16Array,
+};
+use ::arrow::datatypes::{DataType, Field, Int32Type, Schema, TimeUnit};
+use ::arrow::record_batch::RecordBatch;
+use ::parquet::arrow::ArrowWriter;
+use chrono::NaiveDate;
+use std::io::Write;
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+fn timestamp() -> DataType {
+    DataType::Timestamp(TimeUnit::Second, Some("UTC".into()))
+}
+
+fn dictionary() -> DataType {
+    DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+}
+
+/// Column name of a parameter and unit, e.g. `t_c`, `hu_pct`, `wd_card`.
+fn column_name(id: &str, unit: &str) -> String {
+    let unit = match unit {
+        "%" => "pct".to_string(),
+        u => u.to_lowercase(),
+    };
+    if unit.is_empty() {
+        id.to_string()
+    } else {
+        format!("{}_{}", id, unit)
+    }
+}
+
+/// Converts `data` into a single record batch.
+pub fn to_record_batch(data: &Data) -> Result<RecordBatch, Error> {
+    let forecast = data.forecast.as_ref();
+    let areas: Vec<_> = forecast.iter().flat_map(|f| f.areas.iter()).collect();
+    let issued = forecast
+        .and_then(|f| f.issue.as_ref())
+        .and_then(|i| i.datetime_utc())
+        .map(|d| d.timestamp());
+    let rows: Vec<_> = areas
+        .iter()
+        .flat_map(|a| rows(a).into_iter().map(move |tr| (*a, tr)))
+        .collect();
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+
+    let mut fields = vec![
+        Field::new("area_id", DataType::Utf8, true),
+        Field::new("area", DataType::Utf8, true),
+        Field::new("domain", dictionary(), true),
+        Field::new("latitude", DataType::Float64, true),
+        Field::new("longitude", DataType::Float64, true),
+        Field::new("issued", timestamp(), true),
+        Field::new("type", dictionary(), true),
+        Field::new("datetime", timestamp(), true),
+        Field::new("h", DataType::Int32, true),
+        Field::new("day", DataType::Date32, true),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|(a, _)| a.id.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|(a, _)| a.description.as_deref()),
+        )),
+        Arc::new(DictionaryArray::<Int32Type>::from_iter(
+            rows.iter().map(|(a, _)| a.domain.as_deref()),
+        )),
+        Arc::new(Float64Array::from_iter(
+            rows.iter().map(|(a, _)| a.position().map(|p| p.0)),
+        )),
+        Arc::new(Float64Array::from_iter(
+            rows.iter().map(|(a, _)| a.position().map(|p| p.1)),
+        )),
+        Arc::new(TimestampSecondArray::from(vec![issued; rows.len()]).with_timezone("UTC")),
+        Arc::new(DictionaryArray::<Int32Type>::from_iter(
+            rows.iter().map(|(_, tr)| tr.typ.as_deref()),
+        )),
+        Arc::new(
+            TimestampSecondArray::from_iter(
+                rows.iter()
+                    .map(|(_, tr)| tr.datetime_utc().map(|d| d.timestamp())),
+            )
+            .with_timezone("UTC"),
+        ),
+        Arc::new(Int32Array::from_iter(rows.iter().map(|(_, tr)| {
+            tr.h.as_ref().and_then(|h| h.trim().parse().ok())
+        }))),
+        Arc::new(Date32Array::from_iter(rows.iter().map(|(_, tr)| {
+            tr.local_date()
+                .map(|d| d.signed_duration_since(epoch).num_days() as i32)
+        }))),
+    ];
+
+    for (id, unit) in value_columns(data) {
+        let values: Vec<Option<&str>> = rows
+            .iter()
+            .map(|(a, tr)| value(a, &id, &unit, tr))
+            .collect();
+
+        if id == "weather" {
+            let codes: Vec<Option<u16>> = values
+                .iter()
+                .map(|v| v.and_then(|v| v.trim().parse().ok()))
+                .collect();
+            let descriptions = DictionaryArray::<Int32Type>::from_iter(codes.iter().map(|c| {
+                c.and_then(|c| Weather::from_code(c as u32))
+                    .map(|w| w.description_en())
+            }));
+            fields.push(Field::new("weather", DataType::UInt16, true));
+            fields.push(Field::new("weather_description", dictionary(), true));
+            columns.push(Arc::new(UInt16Array::from(codes)));
+            columns.push(Arc::new(descriptions));
+        } else if unit.eq_ignore_ascii_case("CARD") {
+            fields.push(Field::new(column_name(&id, &unit), dictionary(), true));
+            columns.push(Arc::new(DictionaryArray::<Int32Type>::from_iter(values)));
+        } else {
+            let numbers = values
+                .iter()
+                .map(|v| v.and_then(|v| v.trim().parse::<f64>().ok()));
+            fields.push(Field::new(column_name(&id, &unit), DataType::Float64, true));
+            columns.push(Arc::new(Float64Array::from_iter(numbers)));
+        }
+    }
+
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// Writes `data` as a Parquet file to `w`.
+pub fn write_parquet<W: Write + Send>(data: &Data, w: W) -> Result<(), Error> {
+    let batch = to_record_batch(data)?;
+    let mut writer = ArrowWriter::try_new(w, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast_file;
+    use ::arrow::array::Array;
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::fs::File;
+
+    #[test]
+    fn to_record_batch_test() {
+        let data = parse_forecast_file("fixtures/DigitalForecast-Sample.xml").unwrap();
+        let batch = to_record_batch(&data).unwrap();
+        let schema = batch.schema();
+
+        assert_eq!(batch.num_rows(), 20);
+        assert_eq!(
+            schema.field_with_name("datetime").unwrap().data_type(),
+            &timestamp()
+        );
+        assert_eq!(
+            schema.field_with_name("day").unwrap().data_type(),
+            &DataType::Date32
+        );
+        assert_eq!(
+            schema.field_with_name("t_c").unwrap().data_type(),
+            &DataType::Float64
+        );
+        assert_eq!(
+            schema.field_with_name("weather").unwrap().data_type(),
+            &DataType::UInt16
+        );
+        assert_eq!(
+            schema.field_with_name("wd_card").unwrap().data_type(),
+            &dictionary()
+        );
+        assert!(schema.field_with_name("hu_pct").is_ok());
+
+        let t = batch
+            .column_by_name("t_c")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        let datetime = batch
+            .column_by_name("datetime")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<TimestampSecondArray>()
+            .unwrap();
+        assert_eq!(t.value(1), 31.0);
+        assert_eq!(datetime.value(1), 1634623200);
+        // daily rows carry no hourly temperature
+        assert!(t.is_null(8));
+    }
+
+    #[test]
+    fn write_parquet_test() {
+        let data = parse_forecast_file("fixtures/DigitalForecast-Sample.xml").unwrap();
+        let path = std::env::temp_dir().join(format!("bmkgw-cuaca-{}.parquet", std::process::id()));
+        write_parquet(&data, File::create(&path).unwrap()).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let rows: usize = reader.map(|b| b.unwrap().num_rows()).sum();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(rows, 20);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::cuaca::units::{Quantity, Unit, Units};
+use crate::cuaca::Area;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+/// NWS heat index risk categories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatRisk {
+    /// Heat index below 27 °C.
+    Low,
+    /// 27–32 °C, fatigue possible with prolonged exposure.
+    Caution,
+    /// 32–41 °C, heat cramps and exhaustion possible.
+    ExtremeCaution,
+    /// 41–54 °C, heat stroke possible.
+    Danger,
+    /// 54 °C and above, heat stroke highly likely.
+    ExtremeDanger,
+}
+
+impl HeatRisk {
+    pub fn from_heat_index(celsius: f64) -> HeatRisk {
+        if celsius >= 54.0 {
+            HeatRisk::ExtremeDanger
+        } else if celsius >= 41.0 {
+            HeatRisk::Danger
+        } else if celsius >= 32.0 {
+            HeatRisk::ExtremeCaution
+        } else if celsius >= 27.0 {
+            HeatRisk::Caution
+        } else {
+            HeatRisk::Low
+        }
+    }
+}
+
+/// Heat index in °C using the NWS Rothfusz regression with its
+/// low-humidity and high-humidity adjustments.
+pub fn heat_index(celsius: f64, humidity: f64) -> f64 {
+    let t = celsius * 9.0 / 5.0 + 32.0;
+    let rh = humidity;
+
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let hi = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let mut hi = -42.379 + 2.049_015_23 * t + 10.143_331_27 * rh
+            - 0.224_755_41 * t * rh
+            - 0.006_837_83 * t * t
+            - 0.054_817_17 * rh * rh
+            + 0.001_228_74 * t * t * rh
+            + 0.000_852_82 * t * rh * rh
+            - 0.000_001_99 * t * t * rh * rh;
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            hi -= ((13.0 - rh) / 4.0) * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            hi += ((rh - 85.0) / 10.0) * ((87.0 - t) / 5.0);
+        }
+        hi
+    };
+
+    (hi - 32.0) * 5.0 / 9.0
+}
+
+/// Dew point in °C using the Magnus formula.
+pub fn dew_point(celsius: f64, humidity: f64) -> f64 {
+    let (a, b) = (17.62, 243.12);
+    let gamma = (humidity.max(1.0) / 100.0).ln() + a * celsius / (b + celsius);
+
+    b * gamma / (a - gamma)
+}
+
+/// Apparent temperature in °C (Steadman, as used by the Australian Bureau
+/// of Meteorology) for a wind speed in m/s.
+pub fn apparent_temperature(celsius: f64, humidity: f64, wind_speed: f64) -> f64 {
+    let vapour_pressure = humidity / 100.0 * 6.105 * (17.27 * celsius / (237.7 + celsius)).exp();
+
+    celsius + 0.33 * vapour_pressure - 0.70 * wind_speed - 4.00
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comfort {
+    pub datetime: DateTime<Utc>,
+    /// °C
+    pub temperature: f64,
+    /// %
+    pub humidity: f64,
+    /// m/s, when the area has a `ws` value for the same time.
+    pub wind_speed: Option<f64>,
+    pub heat_index: f64,
+    pub dew_point: f64,
+    /// Calm air is assumed when the wind speed is missing.
+    pub apparent_temperature: f64,
+    pub risk: HeatRisk,
+}
+
+impl Comfort {
+    pub fn new(
+        datetime: DateTime<Utc>,
+        temperature: f64,
+        humidity: f64,
+        wind_speed: Option<f64>,
+    ) -> Self {
+        let heat_index = heat_index(temperature, humidity);
+
+        Self {
+            datetime,
+            temperature,
+            humidity,
+            wind_speed,
+            heat_index,
+            dew_point: dew_point(temperature, humidity),
+            apparent_temperature: apparent_temperature(
+                temperature,
+                humidity,
+                wind_speed.unwrap_or(0.0),
+            ),
+            risk: HeatRisk::from_heat_index(heat_index),
+        }
+    }
+
+    pub fn to_json(self) -> JsonValue {
+        json!(self)
+    }
+}
+
+impl Area {
+    /// Comfort metrics for every time range that has both a `t` and a `hu` value.
+    pub fn comfort(&self) -> Vec<Comfort> {
+        let units = Units {
+            speed: Unit::MetresPerSecond,
+            ..Units::default()
+        };
+        let series = |id: &str| {
+            self.parameter(id)
+                .map(|p| p.quantities(&units))
+                .unwrap_or_default()
+        };
+        let humidity = series("hu");
+        let wind = series("ws");
+
+        series("t")
+            .into_iter()
+            .filter_map(|(datetime, t)| {
+                let hu = humidity.iter().find(|(d, _)| *d == datetime)?.1;
+                let ws = wind
+                    .iter()
+                    .find(|(d, _)| *d == datetime)
+                    .map(|(_, q): &(_, Quantity)| q.value);
+                Some(Comfort::new(datetime, t.value, hu.value, ws))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast;
+
+    const SAMPLE: &str = include_str!("../../fixtures/DigitalForecast-Sample.xml");
+
+    #[test]
+    fn heat_index_test() {
+        // NWS table: 90 °F at 70 % is 106 °F
+        let hi = heat_index(32.22, 70.0) * 9.0 / 5.0 + 32.0;
+
+        assert!((hi - 106.0).abs() < 1.0);
+        assert!((heat_index(20.0, 50.0) - 19.4).abs() < 0.5);
+    }
+
+    #[test]
+    fn dew_point_test() {
+        assert!((dew_point(30.0, 70.0) - 23.9).abs() < 0.2);
+        assert!((dew_point(25.0, 100.0) - 25.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn apparent_temperature_test() {
+        let calm = apparent_temperature(30.0, 70.0, 0.0);
+        let windy = apparent_temperature(30.0, 70.0, 5.0);
+
+        assert!((calm - 35.8).abs() < 0.1);
+        assert!((calm - windy - 3.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn heat_risk_test() {
+        assert_eq!(HeatRisk::from_heat_index(26.0), HeatRisk::Low);
+        assert_eq!(HeatRisk::from_heat_index(30.0), HeatRisk::Caution);
+        assert_eq!(HeatRisk::from_heat_index(35.0), HeatRisk::ExtremeCaution);
+        assert_eq!(HeatRisk::from_heat_index(45.0), HeatRisk::Danger);
+        assert_eq!(HeatRisk::from_heat_index(60.0), HeatRisk::ExtremeDanger);
+    }
+
+    #[test]
+    fn area_comfort_test() {
+        let data = parse_forecast(SAMPLE).unwrap();
+        let comfort = data.forecast.unwrap().areas[0].comfort();
+
+        assert_eq!(comfort.len(), 8);
+        assert_eq!(comfort[0].temperature, 27.0);
+        assert_eq!(comfort[0].humidity, 85.0);
+        assert!((comfort[0].wind_speed.unwrap() - 2.572).abs() < 0.01);
+        assert_eq!(comfort[1].risk, HeatRisk::ExtremeCaution);
+    }
+}
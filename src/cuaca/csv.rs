@@ -0,0 +1,147 @@
+//! CSV export of forecasts: one row per area and time range, one column per
+//! parameter and unit, e.g. `t (C)`, `t (F)`, `ws (Kt)`.
+
+use crate::cuaca::{Area, Data, TimeRange};
+use crate::Error;
+use std::io::Write;
+
+const FIXED_COLUMNS: [&str; 9] = [
+    "area_id",
+    "area",
+    "domain",
+    "latitude",
+    "longitude",
+    "type",
+    "datetime",
+    "h",
+    "day",
+];
+
+/// `(parameter id, unit)` of every value column, in order of first appearance.
+pub(crate) fn value_columns(data: &Data) -> Vec<(String, String)> {
+    let mut columns: Vec<(String, String)> = Vec::new();
+    let areas = data.forecast.iter().flat_map(|f| f.areas.iter());
+    for p in areas.flat_map(|a| a.parameters.iter()) {
+        let id = p.id.clone().unwrap_or_default();
+        for v in p.timeranges.iter().flat_map(|tr| tr.values.iter()) {
+            let column = (id.clone(), v.unit.clone().unwrap_or_default());
+            if !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+    }
+    columns
+}
+
+fn header(column: &(String, String)) -> String {
+    match column {
+        (id, unit) if unit.is_empty() => id.clone(),
+        (id, unit) => format!("{} ({})", id, unit),
+    }
+}
+
+/// Time ranges of `area` keyed by type, datetime and day, in order of first appearance.
+pub(crate) fn rows(area: &Area) -> Vec<&TimeRange> {
+    let mut rows: Vec<&TimeRange> = Vec::new();
+    for tr in area.parameters.iter().flat_map(|p| p.timeranges.iter()) {
+        let seen = rows
+            .iter()
+            .any(|r| r.typ == tr.typ && r.datetime == tr.datetime && r.day == tr.day);
+        if !seen {
+            rows.push(tr);
+        }
+    }
+    rows
+}
+
+/// Value of parameter `id` in `unit` for the time range matching `row`.
+pub(crate) fn value<'a>(area: &'a Area, id: &str, unit: &str, row: &TimeRange) -> Option<&'a str> {
+    area.parameter(id)?
+        .timeranges
+        .iter()
+        .find(|tr| tr.typ == row.typ && tr.datetime == row.datetime && tr.day == row.day)?
+        .values
+        .iter()
+        .find(|v| v.unit.as_deref().unwrap_or_default() == unit)?
+        .value
+        .as_deref()
+}
+
+/// Writes `data` as CSV to `w`, one area at a time.
+pub fn write_forecast<W: Write>(data: &Data, w: W) -> Result<(), Error> {
+    let columns = value_columns(data);
+    let mut writer = ::csv::Writer::from_writer(w);
+
+    let headers = FIXED_COLUMNS
+        .iter()
+        .map(|x| x.to_string())
+        .chain(columns.iter().map(header));
+    writer.write_record(headers)?;
+
+    for area in data.forecast.iter().flat_map(|f| f.areas.iter()) {
+        for row in rows(area) {
+            let datetime = row
+                .datetime_utc()
+                .map(|d| d.to_rfc3339())
+                .unwrap_or_default();
+            let mut record = vec![
+                area.id.clone().unwrap_or_default(),
+                area.description.clone().unwrap_or_default(),
+                area.domain.clone().unwrap_or_default(),
+                area.latitude.clone().unwrap_or_default(),
+                area.longitude.clone().unwrap_or_default(),
+                row.typ.clone().unwrap_or_default(),
+                datetime,
+                row.h.clone().unwrap_or_default(),
+                row.day.clone().unwrap_or_default(),
+            ];
+
+            for (id, unit) in columns.iter() {
+                record.push(value(area, id, unit, row).unwrap_or_default().to_string());
+            }
+            writer.write_record(&record)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast;
+
+    #[test]
+    fn write_forecast_test() {
+        let data =
+            parse_forecast(include_str!("../../fixtures/DigitalForecast-Sample.xml")).unwrap();
+        let mut out = Vec::new();
+        write_forecast(&data, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines[0].starts_with(
+            "area_id,area,domain,latitude,longitude,type,datetime,h,day,hu (%),humax (%),tmax (C),tmax (F)"
+        ));
+        assert!(lines[0].contains(",weather (icon),wd (deg),wd (CARD),wd (SEXA),ws (Kt),"));
+        // 2 areas × (8 hourly + 2 daily) time ranges
+        assert_eq!(lines.len(), 1 + 2 * 10);
+
+        let mut reader = ::csv::Reader::from_reader(text.as_bytes());
+        let headers = reader.headers().unwrap().clone();
+        let col = |name: &str| headers.iter().position(|h| h == name).unwrap();
+        let records: Vec<_> = reader.records().map(|r| r.unwrap()).collect();
+
+        let hourly = &records[1];
+        assert_eq!(&hourly[col("area_id")], "501195");
+        assert_eq!(&hourly[col("datetime")], "2021-10-19T06:00:00+00:00");
+        assert_eq!(&hourly[col("t (C)")], "31");
+        assert_eq!(&hourly[col("tmax (C)")], "");
+
+        let daily = records.iter().find(|r| &r[col("type")] == "daily").unwrap();
+        assert_eq!(&daily[col("day")], "20211019");
+        assert_eq!(&daily[col("tmax (C)")], "33");
+        assert_eq!(&daily[col("t (C)")], "");
+    }
+}
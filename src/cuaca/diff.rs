@@ -0,0 +1,311 @@
+use crate::cuaca::{Area, Data, Parameter, TimeRange};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValueChange {
+    pub datetime: String,
+    pub unit: Option<String>,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterDiff {
+    pub id: Option<String>,
+    pub change: Change,
+    /// Keys (`datetime`, or `h` when missing) of time ranges only in the new issuance.
+    pub added: Vec<String>,
+    /// Keys of time ranges only in the old issuance.
+    pub removed: Vec<String>,
+    pub changed: Vec<ValueChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AreaDiff {
+    pub id: Option<String>,
+    pub description: Option<String>,
+    pub change: Change,
+    pub parameters: Vec<ParameterDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataDiff {
+    pub old_issue: Option<String>,
+    pub new_issue: Option<String>,
+    pub areas: Vec<AreaDiff>,
+}
+
+impl DataDiff {
+    pub fn is_empty(&self) -> bool {
+        self.areas.is_empty()
+    }
+
+    pub fn to_json(self) -> JsonValue {
+        json!(self)
+    }
+}
+
+fn issue_timestamp(data: &Data) -> Option<String> {
+    data.forecast
+        .as_ref()
+        .and_then(|f| f.issue.as_ref())
+        .and_then(|i| i.timestamp.clone())
+}
+
+fn areas(data: &Data) -> &[Area] {
+    data.forecast
+        .as_ref()
+        .map(|f| f.areas.as_slice())
+        .unwrap_or_default()
+}
+
+fn time_range_key(t: &TimeRange) -> String {
+    t.datetime
+        .clone()
+        .or_else(|| t.h.clone())
+        .unwrap_or_default()
+}
+
+fn diff_time_range(key: &str, old: &TimeRange, new: &TimeRange) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+
+    for n in new.values.iter() {
+        let o = old.values.iter().find(|o| o.unit == n.unit);
+        let old_value = o.and_then(|o| o.value.clone());
+        if old_value != n.value {
+            changes.push(ValueChange {
+                datetime: key.to_string(),
+                unit: n.unit.clone(),
+                old: old_value,
+                new: n.value.clone(),
+            });
+        }
+    }
+    for o in old.values.iter() {
+        if !new.values.iter().any(|n| n.unit == o.unit) {
+            changes.push(ValueChange {
+                datetime: key.to_string(),
+                unit: o.unit.clone(),
+                old: o.value.clone(),
+                new: None,
+            });
+        }
+    }
+
+    changes
+}
+
+fn diff_parameter(old: &Parameter, new: &Parameter) -> Option<ParameterDiff> {
+    let mut diff = ParameterDiff {
+        id: new.id.clone(),
+        change: Change::Changed,
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+    };
+
+    for n in new.timeranges.iter() {
+        let key = time_range_key(n);
+        match old.timeranges.iter().find(|o| time_range_key(o) == key) {
+            Some(o) => diff.changed.extend(diff_time_range(&key, o, n)),
+            None => diff.added.push(key),
+        }
+    }
+    for o in old.timeranges.iter() {
+        let key = time_range_key(o);
+        if !new.timeranges.iter().any(|n| time_range_key(n) == key) {
+            diff.removed.push(key);
+        }
+    }
+
+    if diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty() {
+        None
+    } else {
+        Some(diff)
+    }
+}
+
+fn whole_parameter(p: &Parameter, change: Change) -> ParameterDiff {
+    let keys = p.timeranges.iter().map(time_range_key).collect();
+    let (added, removed) = match change {
+        Change::Removed => (Vec::new(), keys),
+        _ => (keys, Vec::new()),
+    };
+
+    ParameterDiff {
+        id: p.id.clone(),
+        change,
+        added,
+        removed,
+        changed: Vec::new(),
+    }
+}
+
+fn diff_area(old: &Area, new: &Area) -> Option<AreaDiff> {
+    let mut parameters = Vec::new();
+
+    for n in new.parameters.iter() {
+        match old.parameters.iter().find(|o| o.id == n.id) {
+            Some(o) => parameters.extend(diff_parameter(o, n)),
+            None => parameters.push(whole_parameter(n, Change::Added)),
+        }
+    }
+    for o in old.parameters.iter() {
+        if !new.parameters.iter().any(|n| n.id == o.id) {
+            parameters.push(whole_parameter(o, Change::Removed));
+        }
+    }
+
+    if parameters.is_empty() {
+        None
+    } else {
+        Some(AreaDiff {
+            id: new.id.clone(),
+            description: new.description.clone(),
+            change: Change::Changed,
+            parameters,
+        })
+    }
+}
+
+fn whole_area(a: &Area, change: Change) -> AreaDiff {
+    AreaDiff {
+        id: a.id.clone(),
+        description: a.description.clone(),
+        change,
+        parameters: a
+            .parameters
+            .iter()
+            .map(|p| whole_parameter(p, change))
+            .collect(),
+    }
+}
+
+/// Compares two issuances of the same forecast, matching areas and
+/// parameters by `id`, time ranges by `datetime` and values by `unit`.
+/// Only areas with at least one difference are reported.
+pub fn compare(old: &Data, new: &Data) -> DataDiff {
+    let old_areas = areas(old);
+    let new_areas = areas(new);
+    let mut diffs = Vec::new();
+
+    for n in new_areas.iter() {
+        match old_areas.iter().find(|o| o.id == n.id) {
+            Some(o) => diffs.extend(diff_area(o, n)),
+            None => diffs.push(whole_area(n, Change::Added)),
+        }
+    }
+    for o in old_areas.iter() {
+        if !new_areas.iter().any(|n| n.id == o.id) {
+            diffs.push(whole_area(o, Change::Removed));
+        }
+    }
+
+    DataDiff {
+        old_issue: issue_timestamp(old),
+        new_issue: issue_timestamp(new),
+        areas: diffs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast;
+
+    const SAMPLE: &str = include_str!("../../fixtures/DigitalForecast-Sample.xml");
+
+    fn sample() -> Data {
+        parse_forecast(SAMPLE).unwrap()
+    }
+
+    fn area_mut(data: &mut Data, idx: usize) -> &mut Area {
+        &mut data.forecast.as_mut().unwrap().areas[idx]
+    }
+
+    #[test]
+    fn compare_identical_test() {
+        let diff = compare(&sample(), &sample());
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.new_issue, Some("20211019041211".to_string()));
+    }
+
+    #[test]
+    fn compare_changed_value_test() {
+        let old = sample();
+        let mut new = sample();
+        let weather = &mut area_mut(&mut new, 0).parameters[6];
+        weather.timeranges[0].values[0].value = Some("61".to_string());
+
+        let diff = compare(&old, &new);
+        let parameter = &diff.areas[0].parameters[0];
+
+        assert_eq!(diff.areas.len(), 1);
+        assert_eq!(diff.areas[0].id, Some("501195".to_string()));
+        assert_eq!(parameter.id, Some("weather".to_string()));
+        assert_eq!(
+            parameter.changed,
+            vec![ValueChange {
+                datetime: "202110190000".to_string(),
+                unit: Some("icon".to_string()),
+                old: Some("3".to_string()),
+                new: Some("61".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn compare_shifted_time_ranges_test() {
+        let old = sample();
+        let mut new = sample();
+        let hu = &mut area_mut(&mut new, 1).parameters[0];
+        hu.timeranges.remove(0);
+        let mut last = hu.timeranges.last().unwrap().clone();
+        last.datetime = Some("202110210000".to_string());
+        hu.timeranges.push(last);
+
+        let diff = compare(&old, &new);
+        let parameter = &diff.areas[0].parameters[0];
+
+        assert_eq!(parameter.added, vec!["202110210000".to_string()]);
+        assert_eq!(parameter.removed, vec!["202110190000".to_string()]);
+        assert!(parameter.changed.is_empty());
+    }
+
+    #[test]
+    fn compare_added_and_removed_area_test() {
+        let old = sample();
+        let mut new = sample();
+        new.forecast.as_mut().unwrap().areas.remove(1);
+
+        let removed = compare(&old, &new);
+        let added = compare(&new, &old);
+
+        assert_eq!(removed.areas[0].change, Change::Removed);
+        assert_eq!(added.areas[0].change, Change::Added);
+        assert_eq!(added.areas[0].parameters[0].added.len(), 8);
+    }
+
+    #[test]
+    fn diff_to_json_test() {
+        let old = sample();
+        let mut new = sample();
+        area_mut(&mut new, 0).parameters[5].timeranges[1].values[0].value = Some("28".to_string());
+
+        let json = compare(&old, &new).to_json();
+
+        assert_eq!(json["areas"][0]["change"], "changed");
+        assert_eq!(json["areas"][0]["parameters"][0]["changed"][0]["old"], "31");
+        assert_eq!(json["areas"][0]["parameters"][0]["changed"][0]["new"], "28");
+    }
+}
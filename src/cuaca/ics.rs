@@ -0,0 +1,324 @@
+//! iCalendar (RFC 5545) export of area forecasts.
+//!
+//! Every hourly time range becomes an event and every daily one an all-day
+//! event. UIDs depend only on the area id and the time range, so importing
+//! a newer issuance updates the events of an earlier one. `SEQUENCE` grows
+//! with `dtstamp` so that clients accept the update.
+
+use crate::cuaca::units::{Quantity, Unit};
+use crate::cuaca::{Area, TimeRange, Weather};
+use crate::Error;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::io::Write;
+
+const PRODID: &str = "-//bmkgw//Forecast//EN";
+const UID_DOMAIN: &str = "bmkgw";
+const DEFAULT_DURATION_HOURS: i64 = 6;
+
+/// Escapes a TEXT value.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line at 75 octets, without splitting characters.
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+fn utc(d: DateTime<Utc>) -> String {
+    d.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Minutes since the Unix epoch, which unlike seconds fit the 32-bit
+/// `SEQUENCE` integer for the foreseeable future.
+fn sequence(d: DateTime<Utc>) -> i64 {
+    d.timestamp().div_euclid(60)
+}
+
+fn value(area: &Area, id: &str, unit: &str, pick: impl Fn(&TimeRange) -> bool) -> Option<String> {
+    let tr = area.parameter(id)?.timeranges.iter().find(|tr| pick(tr))?;
+    tr.find_value(unit)?.value.clone()
+}
+
+fn number(
+    area: &Area,
+    id: &str,
+    unit: Unit,
+    pick: impl Fn(&TimeRange) -> bool,
+) -> Option<Quantity> {
+    let tr = area.parameter(id)?.timeranges.iter().find(|tr| pick(tr))?;
+    Quantity::from_value(tr.find_value(unit.symbol())?)
+}
+
+fn hourly_summary(area: &Area, datetime: DateTime<Utc>) -> (String, String) {
+    let at = |tr: &TimeRange| tr.day.is_none() && tr.datetime_utc() == Some(datetime);
+    let weather = value(area, "weather", "icon", at)
+        .and_then(|v| v.trim().parse().ok())
+        .and_then(Weather::from_code);
+    let t = number(area, "t", Unit::Celsius, at);
+    let hu = number(area, "hu", Unit::Percent, at);
+    let ws = number(area, "ws", Unit::Knot, at);
+    let wd = value(area, "wd", "CARD", at);
+
+    let mut summary = Vec::new();
+    if let Some(w) = weather {
+        summary.push(w.description_en().to_string());
+    }
+    if let Some(t) = t {
+        summary.push(t.to_string());
+    }
+    if let Some(ws) = ws {
+        match wd {
+            Some(ref wd) => summary.push(format!("wind {} {}", wd, ws)),
+            None => summary.push(format!("wind {}", ws)),
+        }
+    }
+
+    let mut description = Vec::new();
+    if let Some(w) = weather {
+        description.push(format!("Cuaca: {}", w.description_id()));
+    }
+    if let Some(hu) = hu {
+        description.push(format!("Kelembapan: {}", hu));
+    }
+    (summary.join(", "), description.join("\n"))
+}
+
+fn daily_summary(area: &Area, day: &str) -> String {
+    let on = |tr: &TimeRange| tr.day.as_deref() == Some(day);
+    let mut parts = Vec::new();
+    if let Some(tmin) = number(area, "tmin", Unit::Celsius, on) {
+        parts.push(format!("min {}", tmin));
+    }
+    if let Some(tmax) = number(area, "tmax", Unit::Celsius, on) {
+        parts.push(format!("max {}", tmax));
+    }
+    if let (Some(humin), Some(humax)) = (
+        number(area, "humin", Unit::Percent, on),
+        number(area, "humax", Unit::Percent, on),
+    ) {
+        parts.push(format!("humidity {}–{}", humin, humax));
+    }
+    parts.join(", ")
+}
+
+/// Writes the `VEVENT`s of `area`; `dtstamp` is usually the issue time.
+fn write_events<W: Write>(area: &Area, dtstamp: DateTime<Utc>, w: &mut W) -> Result<(), Error> {
+    let id = area.id.as_deref().unwrap_or_default();
+    let name = area.description.as_deref().unwrap_or(id);
+    let location = match area.domain {
+        Some(ref domain) => format!("{}, {}", name, domain),
+        None => name.to_string(),
+    };
+    let timeranges = area.parameters.iter().flat_map(|p| p.timeranges.iter());
+
+    let mut hourly: Vec<DateTime<Utc>> = timeranges
+        .clone()
+        .filter(|tr| tr.day.is_none())
+        .filter_map(|tr| tr.datetime_utc())
+        .collect();
+    hourly.sort();
+    hourly.dedup();
+    let mut daily: Vec<&str> = timeranges.filter_map(|tr| tr.day.as_deref()).collect();
+    daily.sort_unstable();
+    daily.dedup();
+
+    let mut lines = Vec::new();
+    for (i, start) in hourly.iter().enumerate() {
+        let end = hourly
+            .get(i + 1)
+            .cloned()
+            .unwrap_or(*start + Duration::hours(DEFAULT_DURATION_HOURS));
+        let (summary, description) = hourly_summary(area, *start);
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-{}@{}", id, utc(*start), UID_DOMAIN));
+        lines.push(format!("DTSTAMP:{}", utc(dtstamp)));
+        lines.push(format!("LAST-MODIFIED:{}", utc(dtstamp)));
+        lines.push(format!("SEQUENCE:{}", sequence(dtstamp)));
+        lines.push(format!("DTSTART:{}", utc(*start)));
+        lines.push(format!("DTEND:{}", utc(end)));
+        lines.push(format!("SUMMARY:{}", escape(&summary)));
+        if !description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&description)));
+        }
+        lines.push(format!("LOCATION:{}", escape(&location)));
+        if let Some((lat, lon)) = area.position() {
+            lines.push(format!("GEO:{};{}", lat, lon));
+        }
+        lines.push("TRANSP:TRANSPARENT".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    for day in daily {
+        let date = match NaiveDate::parse_from_str(day, "%Y%m%d") {
+            Ok(date) => date,
+            Err(_) => continue,
+        };
+        let next = date + Duration::days(1);
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-{}@{}", id, day, UID_DOMAIN));
+        lines.push(format!("DTSTAMP:{}", utc(dtstamp)));
+        lines.push(format!("LAST-MODIFIED:{}", utc(dtstamp)));
+        lines.push(format!("SEQUENCE:{}", sequence(dtstamp)));
+        lines.push(format!("DTSTART;VALUE=DATE:{}", date.format("%Y%m%d")));
+        lines.push(format!("DTEND;VALUE=DATE:{}", next.format("%Y%m%d")));
+        lines.push(format!("SUMMARY:{}", escape(&daily_summary(area, day))));
+        lines.push(format!("LOCATION:{}", escape(&location)));
+        lines.push("TRANSP:TRANSPARENT".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+
+    for line in lines {
+        w.write_all(fold(&line).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes one calendar holding the events of every area.
+pub fn write_calendar<'a, W, I>(areas: I, dtstamp: DateTime<Utc>, mut w: W) -> Result<(), Error>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Area>,
+{
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        &format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN",
+    ] {
+        w.write_all(fold(line).as_bytes())?;
+    }
+    for area in areas {
+        write_events(area, dtstamp, &mut w)?;
+    }
+    w.write_all(fold("END:VCALENDAR").as_bytes())?;
+    Ok(())
+}
+
+impl Area {
+    /// The area's forecast as an `.ics` calendar.
+    pub fn to_ics(&self, dtstamp: DateTime<Utc>) -> String {
+        let mut out = Vec::new();
+        // writing to a Vec cannot fail
+        let _ = write_calendar(std::iter::once(self), dtstamp, &mut out);
+        String::from_utf8_lossy(&out).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast;
+
+    fn data() -> crate::cuaca::Data {
+        parse_forecast(include_str!("../../fixtures/DigitalForecast-Sample.xml")).unwrap()
+    }
+
+    #[test]
+    fn to_ics_test() {
+        let data = data();
+        let forecast = data.forecast.unwrap();
+        let issued = forecast.issue.as_ref().unwrap().datetime_utc().unwrap();
+        let ics = forecast.areas[0].to_ics(issued);
+        let lines: Vec<&str> = ics.split("\r\n").collect();
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(
+            lines.iter().filter(|l| **l == "BEGIN:VEVENT").count(),
+            8 + 2
+        );
+        assert!(lines.iter().all(|l| l.len() <= 75));
+
+        let start = lines
+            .iter()
+            .position(|l| *l == "UID:501195-20211019T060000Z@bmkgw")
+            .unwrap();
+        let event = &lines[start..start + 10];
+        assert!(event.contains(&"DTSTAMP:20211019T041211Z"));
+        assert!(event.contains(&"SEQUENCE:27243612"));
+        assert!(event.contains(&"DTSTART:20211019T060000Z"));
+        assert!(event.contains(&"DTEND:20211019T120000Z"));
+        assert!(event.contains(&"LOCATION:Jakarta Pusat\\, DKI Jakarta"));
+        assert!(event
+            .iter()
+            .any(|l| l.starts_with("SUMMARY:") && l.contains("31.0°C")));
+
+        assert!(ics.contains("UID:501195-20211019@bmkgw\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20211019\r\nDTEND;VALUE=DATE:20211020\r\n"));
+        assert!(ics.contains("SUMMARY:min 25.0°C\\, max 33.0°C"));
+    }
+
+    #[test]
+    fn stable_uid_test() {
+        let data = data();
+        let area = &data.forecast.unwrap().areas[0];
+        let uids = |ics: String| -> Vec<String> {
+            ics.lines()
+                .filter(|l| l.starts_with("UID:"))
+                .map(|l| l.to_string())
+                .collect()
+        };
+        let now = Utc::now();
+
+        assert_eq!(
+            uids(area.to_ics(now)),
+            uids(area.to_ics(now + Duration::hours(6)))
+        );
+    }
+
+    #[test]
+    fn sequence_increases_test() {
+        let data = data();
+        let area = &data.forecast.unwrap().areas[0];
+        let sequences = |ics: String| -> Vec<i64> {
+            ics.lines()
+                .filter_map(|l| l.strip_prefix("SEQUENCE:"))
+                .map(|l| l.parse().unwrap())
+                .collect()
+        };
+        let now = Utc::now();
+        let first = sequences(area.to_ics(now));
+        let second = sequences(area.to_ics(now + Duration::hours(3)));
+
+        assert_eq!(first.len(), 10);
+        assert!(first.iter().zip(second.iter()).all(|(a, b)| a < b));
+        assert!(second.iter().all(|s| *s <= i64::from(i32::MAX)));
+    }
+
+    #[test]
+    fn write_calendar_test() {
+        let data = data();
+        let areas = &data.forecast.unwrap().areas;
+        let mut out = Vec::new();
+        write_calendar(areas, Utc::now(), &mut out).unwrap();
+        let ics = String::from_utf8(out).unwrap();
+
+        assert_eq!(ics.matches("BEGIN:VCALENDAR").count(), 1);
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 20);
+        assert!(ics.contains("UID:5002220-20211019T000000Z@bmkgw"));
+    }
+
+    #[test]
+    fn fold_test() {
+        let line = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|l| l.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+}
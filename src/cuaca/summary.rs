@@ -0,0 +1,274 @@
+use crate::cuaca::{Area, Weather};
+use crate::TimeZone;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::collections::{BTreeMap, HashMap};
+
+/// Forecast of an area condensed to one local calendar day.
+///
+/// Temperatures are in °C, humidity in % and wind speed in knots.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub time_zone: TimeZone,
+    pub temperature_min: Option<f64>,
+    pub temperature_max: Option<f64>,
+    pub humidity_min: Option<f64>,
+    pub humidity_max: Option<f64>,
+    /// Most frequent condition of the day, the more severe one on ties.
+    pub weather_dominant: Option<Weather>,
+    pub weather_worst: Option<Weather>,
+    /// Most frequent cardinal wind direction, the stronger one on ties.
+    pub wind_direction: Option<String>,
+    pub wind_speed_mean: Option<f64>,
+    pub wind_speed_max: Option<f64>,
+}
+
+impl DailySummary {
+    fn new(date: NaiveDate, time_zone: TimeZone) -> Self {
+        Self {
+            date,
+            time_zone,
+            temperature_min: None,
+            temperature_max: None,
+            humidity_min: None,
+            humidity_max: None,
+            weather_dominant: None,
+            weather_worst: None,
+            wind_direction: None,
+            wind_speed_mean: None,
+            wind_speed_max: None,
+        }
+    }
+
+    pub fn to_json(self) -> JsonValue {
+        json!(self)
+    }
+}
+
+#[derive(Default)]
+struct Day {
+    temperature: Vec<f64>,
+    humidity: Vec<f64>,
+    weather: Vec<Weather>,
+    wind: Vec<(Option<String>, f64)>,
+}
+
+fn min(xs: &[f64]) -> Option<f64> {
+    xs.iter()
+        .cloned()
+        .fold(None, |acc, x| Some(acc.map_or(x, |a: f64| a.min(x))))
+}
+
+fn max(xs: &[f64]) -> Option<f64> {
+    xs.iter()
+        .cloned()
+        .fold(None, |acc, x| Some(acc.map_or(x, |a: f64| a.max(x))))
+}
+
+fn dominant_weather(xs: &[Weather]) -> Option<Weather> {
+    let mut counts: HashMap<Weather, usize> = HashMap::new();
+    for w in xs {
+        *counts.entry(*w).or_insert(0) += 1;
+    }
+
+    counts
+        .into_iter()
+        .max_by_key(|(w, n)| (*n, w.severity()))
+        .map(|(w, _)| w)
+}
+
+fn prevailing_wind(xs: &[(Option<String>, f64)]) -> Option<String> {
+    let mut counts: BTreeMap<&str, (usize, f64)> = BTreeMap::new();
+    for (card, speed) in xs {
+        if let Some(card) = card {
+            let entry = counts.entry(card.as_str()).or_insert((0, 0.0));
+            entry.0 += 1;
+            entry.1 = entry.1.max(*speed);
+        }
+    }
+
+    counts
+        .into_iter()
+        .max_by(|a, b| {
+            (a.1 .0, a.1 .1)
+                .partial_cmp(&(b.1 .0, b.1 .1))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                // on a tie the alphabetically first direction wins
+                .then_with(|| b.0.cmp(a.0))
+        })
+        .map(|(card, _)| card.to_string())
+}
+
+impl Area {
+    /// Per-day summaries in the area's own time zone, see [`Area::time_zone`].
+    pub fn daily_summaries(&self) -> Vec<DailySummary> {
+        self.daily_summaries_in(self.time_zone())
+    }
+
+    /// Per-day summaries with day boundaries taken in `tz`.
+    ///
+    /// Hourly `t`, `hu`, `weather`, `wd` and `ws` values are grouped by the
+    /// local date of their `datetime`; daily `tmin`, `tmax`, `humin` and
+    /// `humax` values by their `day` attribute and widen the min/max range.
+    pub fn daily_summaries_in(&self, tz: TimeZone) -> Vec<DailySummary> {
+        let offset = tz.utc_offset();
+        let mut days: BTreeMap<NaiveDate, Day> = BTreeMap::new();
+
+        for parameter in self.parameters.iter() {
+            let id = match parameter.id.as_deref() {
+                Some(id) => id,
+                None => continue,
+            };
+            for time_range in parameter.timeranges.iter() {
+                let date = match time_range.local_date().or_else(|| {
+                    time_range
+                        .datetime_utc()
+                        .map(|d| d.with_timezone(&offset).date_naive())
+                }) {
+                    Some(date) => date,
+                    None => continue,
+                };
+                let day = days.entry(date).or_default();
+
+                match id {
+                    "t" | "tmin" | "tmax" => day
+                        .temperature
+                        .extend(time_range.find_value("C").and_then(|v| v.as_f64())),
+                    "hu" | "humin" | "humax" => day
+                        .humidity
+                        .extend(time_range.values.first().and_then(|v| v.as_f64())),
+                    "weather" => day
+                        .weather
+                        .extend(time_range.values.first().and_then(Weather::from_value)),
+                    _ => (),
+                }
+            }
+        }
+
+        let direction = self.parameter("wd");
+        if let Some(speed) = self.parameter("ws") {
+            for time_range in speed.timeranges.iter() {
+                let (date, knots) = match (
+                    time_range.datetime_utc(),
+                    time_range.find_value("Kt").and_then(|v| v.as_f64()),
+                ) {
+                    (Some(d), Some(k)) => (d.with_timezone(&offset).date_naive(), k),
+                    _ => continue,
+                };
+                let card = direction
+                    .and_then(|p| {
+                        p.timeranges
+                            .iter()
+                            .find(|t| t.datetime == time_range.datetime)
+                    })
+                    .and_then(|t| t.find_value("CARD"))
+                    .and_then(|v| v.value.clone());
+                days.entry(date).or_default().wind.push((card, knots));
+            }
+        }
+
+        days.into_iter()
+            .map(|(date, day)| {
+                let mut summary = DailySummary::new(date, tz);
+                let speeds = day.wind.iter().map(|(_, s)| *s).collect::<Vec<_>>();
+
+                summary.temperature_min = min(&day.temperature);
+                summary.temperature_max = max(&day.temperature);
+                summary.humidity_min = min(&day.humidity);
+                summary.humidity_max = max(&day.humidity);
+                summary.weather_dominant = dominant_weather(&day.weather);
+                summary.weather_worst = day.weather.iter().cloned().max_by_key(|w| w.severity());
+                summary.wind_direction = prevailing_wind(&day.wind);
+                if !speeds.is_empty() {
+                    summary.wind_speed_mean =
+                        Some(speeds.iter().sum::<f64>() / speeds.len() as f64);
+                }
+                summary.wind_speed_max = max(&speeds);
+
+                summary
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast;
+
+    const SAMPLE: &str = include_str!("../../fixtures/DigitalForecast-Sample.xml");
+
+    fn area(idx: usize) -> Area {
+        parse_forecast(SAMPLE).unwrap().forecast.unwrap().areas[idx].clone()
+    }
+
+    #[test]
+    fn daily_summaries_test() {
+        let summaries = area(0).daily_summaries();
+        let first = &summaries[0];
+
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(first.date, NaiveDate::from_ymd_opt(2021, 10, 19).unwrap());
+        assert_eq!(first.time_zone, TimeZone::WIB);
+        assert_eq!(first.temperature_min, Some(25.0));
+        assert_eq!(first.temperature_max, Some(33.0));
+        assert_eq!(first.humidity_min, Some(60.0));
+        assert_eq!(first.humidity_max, Some(95.0));
+        assert_eq!(first.weather_worst, Some(Weather::LightRain));
+        assert_eq!(first.wind_direction, Some("W".to_string()));
+        assert_eq!(first.wind_speed_max, Some(12.0));
+    }
+
+    #[test]
+    fn daily_summaries_day_boundary_test() {
+        // 18:00 UTC is already the next day in WIB
+        let wib = area(0).daily_summaries_in(TimeZone::WIB);
+        let wit = area(0).daily_summaries_in(TimeZone::WIT);
+
+        assert_eq!(wib[1].weather_worst, Some(Weather::Thunderstorm));
+        assert_eq!(wib[2].weather_dominant, Some(Weather::MostlyCloudy));
+        assert_eq!(wit[0].time_zone, TimeZone::WIT);
+        assert_eq!(wit.len(), 3);
+    }
+
+    #[test]
+    fn dominant_weather_tie_test() {
+        let xs = vec![Weather::Clear, Weather::Rain, Weather::Rain, Weather::Clear];
+
+        assert_eq!(dominant_weather(&xs), Some(Weather::Rain));
+    }
+
+    #[test]
+    fn prevailing_wind_tie_test() {
+        let wind = |card: &str, speed| (Some(card.to_string()), speed);
+        let xs = vec![wind("S", 5.0), wind("N", 5.0), wind("E", 2.0)];
+
+        for _ in 0..8 {
+            assert_eq!(prevailing_wind(&xs), Some("N".to_string()));
+        }
+        assert_eq!(
+            prevailing_wind(&[wind("S", 5.0), wind("N", 3.0)]),
+            Some("S".to_string())
+        );
+    }
+
+    #[test]
+    fn area_time_zone_test() {
+        let mut a = area(0);
+        assert_eq!(a.time_zone(), TimeZone::WIB);
+
+        a.domain = Some("Sulawesi Selatan".to_string());
+        assert_eq!(a.time_zone(), TimeZone::WITA);
+
+        a.domain = None;
+        a.longitude = Some("140.7".to_string());
+        assert_eq!(a.time_zone(), TimeZone::WIT);
+
+        // an unrecognised domain falls back to the longitude
+        a.domain = Some("Unknown".to_string());
+        a.longitude = Some("119.4".to_string());
+        assert_eq!(a.time_zone(), TimeZone::WITA);
+    }
+}
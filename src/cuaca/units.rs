@@ -0,0 +1,372 @@
+use crate::cuaca::{Parameter, TimeRange, Value};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::fmt;
+
+const CARDINALS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW",
+    "NNW",
+];
+
+/// Units used by the `unit` attribute of forecast values, plus Beaufort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Unit {
+    Celsius,
+    Fahrenheit,
+    Percent,
+    Knot,
+    MilesPerHour,
+    KilometresPerHour,
+    MetresPerSecond,
+    Beaufort,
+    Degree,
+    Cardinal,
+    Sexagesimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dimension {
+    Temperature,
+    Humidity,
+    Speed,
+    Direction,
+}
+
+impl Unit {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str<T: Borrow<str>>(s: T) -> Option<Unit> {
+        match s.borrow().trim().to_lowercase().as_ref() {
+            "c" => Some(Unit::Celsius),
+            "f" => Some(Unit::Fahrenheit),
+            "%" => Some(Unit::Percent),
+            "kt" => Some(Unit::Knot),
+            "mph" => Some(Unit::MilesPerHour),
+            "kph" => Some(Unit::KilometresPerHour),
+            "ms" => Some(Unit::MetresPerSecond),
+            "bft" => Some(Unit::Beaufort),
+            "deg" => Some(Unit::Degree),
+            "card" => Some(Unit::Cardinal),
+            "sexa" => Some(Unit::Sexagesimal),
+            _ => None,
+        }
+    }
+    /// Spelling of the unit in BMKG documents.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Celsius => "C",
+            Unit::Fahrenheit => "F",
+            Unit::Percent => "%",
+            Unit::Knot => "Kt",
+            Unit::MilesPerHour => "MPH",
+            Unit::KilometresPerHour => "KPH",
+            Unit::MetresPerSecond => "MS",
+            Unit::Beaufort => "Bft",
+            Unit::Degree => "deg",
+            Unit::Cardinal => "CARD",
+            Unit::Sexagesimal => "SEXA",
+        }
+    }
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            Unit::Celsius | Unit::Fahrenheit => Dimension::Temperature,
+            Unit::Percent => Dimension::Humidity,
+            Unit::Knot
+            | Unit::MilesPerHour
+            | Unit::KilometresPerHour
+            | Unit::MetresPerSecond
+            | Unit::Beaufort => Dimension::Speed,
+            Unit::Degree | Unit::Cardinal | Unit::Sexagesimal => Dimension::Direction,
+        }
+    }
+}
+
+/// A forecast value with its unit.
+///
+/// Directions are always held in decimal degrees; `Cardinal` and
+/// `Sexagesimal` only change how the value is parsed and displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: Unit,
+}
+
+fn cardinal_to_degrees(s: &str) -> Option<f64> {
+    let s = s.trim().to_uppercase();
+    CARDINALS
+        .iter()
+        .position(|c| *c == s)
+        .map(|i| i as f64 * 22.5)
+}
+
+fn degrees_to_cardinal(deg: f64) -> &'static str {
+    let i = (deg.rem_euclid(360.0) / 22.5).round() as usize % 16;
+    CARDINALS[i]
+}
+
+/// Accepts plain decimal degrees as well as `D°M'S"` notation.
+fn sexagesimal_to_degrees(s: &str) -> Option<f64> {
+    let parts = s
+        .split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .filter(|x| !x.is_empty())
+        .map(|x| x.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+
+    match parts.as_slice() {
+        [d] => Some(*d),
+        [d, m] => Some(d + m / 60.0),
+        [d, m, s] => Some(d + m / 60.0 + s / 3600.0),
+        _ => None,
+    }
+}
+
+fn to_metres_per_second(value: f64, unit: Unit) -> f64 {
+    match unit {
+        Unit::Knot => value * 1852.0 / 3600.0,
+        Unit::MilesPerHour => value * 0.44704,
+        Unit::KilometresPerHour => value / 3.6,
+        Unit::Beaufort => 0.836 * value.max(0.0).powf(1.5),
+        _ => value,
+    }
+}
+
+fn from_metres_per_second(ms: f64, unit: Unit) -> f64 {
+    match unit {
+        Unit::Knot => ms * 3600.0 / 1852.0,
+        Unit::MilesPerHour => ms / 0.44704,
+        Unit::KilometresPerHour => ms * 3.6,
+        Unit::Beaufort => (ms.max(0.0) / 0.836).powf(2.0 / 3.0).round().min(12.0),
+        _ => ms,
+    }
+}
+
+impl Quantity {
+    pub fn new(value: f64, unit: Unit) -> Self {
+        Self { value, unit }
+    }
+    /// Reads a forecast value, `None` for unknown units (e.g. weather
+    /// `icon`) or values that do not parse.
+    pub fn from_value(v: &Value) -> Option<Quantity> {
+        let unit = Unit::from_str(v.unit.as_deref()?)?;
+        let raw = v.value.as_deref()?;
+        let value = match unit {
+            Unit::Cardinal => cardinal_to_degrees(raw)?,
+            Unit::Sexagesimal => sexagesimal_to_degrees(raw)?,
+            _ => raw.trim().parse().ok()?,
+        };
+
+        Some(Quantity::new(value, unit))
+    }
+    /// Converts to another unit of the same dimension.
+    pub fn to(&self, unit: Unit) -> Option<Quantity> {
+        if self.unit.dimension() != unit.dimension() {
+            return None;
+        }
+
+        let value = match (self.unit, unit) {
+            (a, b) if a == b => self.value,
+            (Unit::Celsius, Unit::Fahrenheit) => self.value * 9.0 / 5.0 + 32.0,
+            (Unit::Fahrenheit, Unit::Celsius) => (self.value - 32.0) * 5.0 / 9.0,
+            (a, b) if a.dimension() == Dimension::Speed => {
+                from_metres_per_second(to_metres_per_second(self.value, a), b)
+            }
+            _ => self.value,
+        };
+
+        Some(Quantity::new(value, unit))
+    }
+    /// Beaufort force of a wind speed.
+    pub fn beaufort(&self) -> Option<u8> {
+        self.to(Unit::Beaufort).map(|q| q.value as u8)
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.unit {
+            Unit::Cardinal => write!(f, "{}", degrees_to_cardinal(self.value)),
+            Unit::Sexagesimal => {
+                let total = (self.value.rem_euclid(360.0) * 3600.0).round() as u64;
+                write!(
+                    f,
+                    "{}°{:02}'{:02}\"",
+                    total / 3600,
+                    total % 3600 / 60,
+                    total % 60
+                )
+            }
+            Unit::Celsius | Unit::Fahrenheit => {
+                write!(f, "{:.1}°{}", self.value, self.unit.symbol())
+            }
+            Unit::Percent => write!(f, "{:.0}%", self.value),
+            Unit::Beaufort => write!(f, "{:.0} {}", self.value, self.unit.symbol()),
+            Unit::Degree => write!(f, "{:.0}°", self.value),
+            _ => write!(f, "{:.1} {}", self.value, self.unit.symbol()),
+        }
+    }
+}
+
+/// Preferred units when reading typed forecast values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Units {
+    pub temperature: Unit,
+    pub speed: Unit,
+    pub direction: Unit,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self {
+            temperature: Unit::Celsius,
+            speed: Unit::Knot,
+            direction: Unit::Degree,
+        }
+    }
+}
+
+impl Units {
+    pub fn metric() -> Self {
+        Self {
+            temperature: Unit::Celsius,
+            speed: Unit::KilometresPerHour,
+            direction: Unit::Cardinal,
+        }
+    }
+    pub fn imperial() -> Self {
+        Self {
+            temperature: Unit::Fahrenheit,
+            speed: Unit::MilesPerHour,
+            direction: Unit::Cardinal,
+        }
+    }
+    pub fn preferred(&self, dimension: Dimension) -> Unit {
+        match dimension {
+            Dimension::Temperature => self.temperature,
+            Dimension::Humidity => Unit::Percent,
+            Dimension::Speed => self.speed,
+            Dimension::Direction => self.direction,
+        }
+    }
+}
+
+impl TimeRange {
+    /// The value of this time range in the preferred unit, taken as
+    /// published when available and converted otherwise.
+    pub fn quantity(&self, units: &Units) -> Option<Quantity> {
+        let quantities = self
+            .values
+            .iter()
+            .filter_map(Quantity::from_value)
+            .collect::<Vec<_>>();
+        let first = quantities.first()?;
+        let unit = units.preferred(first.unit.dimension());
+
+        quantities
+            .iter()
+            .find(|q| q.unit == unit)
+            .cloned()
+            .or_else(|| first.to(unit))
+    }
+}
+
+impl Parameter {
+    /// Typed series of this parameter, see [`TimeRange::quantity`].
+    pub fn quantities(&self, units: &Units) -> Vec<(DateTime<Utc>, Quantity)> {
+        self.timeranges
+            .iter()
+            .filter_map(|t| Some((t.datetime_utc()?, t.quantity(units)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast;
+
+    const SAMPLE: &str = include_str!("../../fixtures/DigitalForecast-Sample.xml");
+
+    fn approx(a: f64, b: f64) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn temperature_conversion_test() {
+        let c = Quantity::new(31.0, Unit::Celsius);
+        let f = c.to(Unit::Fahrenheit).unwrap();
+
+        assert!(approx(f.value, 87.8));
+        assert!(approx(f.to(Unit::Celsius).unwrap().value, 31.0));
+        assert_eq!(c.to(Unit::Knot), None);
+    }
+
+    #[test]
+    fn speed_conversion_test() {
+        let kt = Quantity::new(10.0, Unit::Knot);
+
+        assert!(approx(kt.to(Unit::KilometresPerHour).unwrap().value, 18.52));
+        assert!(approx(kt.to(Unit::MetresPerSecond).unwrap().value, 5.144));
+        assert!(approx(kt.to(Unit::MilesPerHour).unwrap().value, 11.508));
+    }
+
+    #[test]
+    fn beaufort_test() {
+        assert_eq!(
+            Quantity::new(0.2, Unit::MetresPerSecond).beaufort(),
+            Some(0)
+        );
+        assert_eq!(Quantity::new(10.0, Unit::Knot).beaufort(), Some(3));
+        assert_eq!(Quantity::new(28.0, Unit::Knot).beaufort(), Some(7));
+        assert_eq!(
+            Quantity::new(200.0, Unit::KilometresPerHour).beaufort(),
+            Some(12)
+        );
+        assert_eq!(Quantity::new(30.0, Unit::Celsius).beaufort(), None);
+    }
+
+    #[test]
+    fn direction_test() {
+        let card = Value {
+            unit: Some("CARD".to_string()),
+            value: Some("SW".to_string()),
+        };
+        let sexa = Value {
+            unit: Some("SEXA".to_string()),
+            value: Some("112°30'00\"".to_string()),
+        };
+        let deg = Quantity::from_value(&card)
+            .unwrap()
+            .to(Unit::Degree)
+            .unwrap();
+
+        assert_eq!(deg.value, 225.0);
+        assert_eq!(Quantity::from_value(&sexa).unwrap().value, 112.5);
+        assert_eq!(Quantity::new(100.0, Unit::Cardinal).to_string(), "E");
+        assert_eq!(
+            Quantity::new(112.5, Unit::Sexagesimal).to_string(),
+            "112°30'00\""
+        );
+    }
+
+    #[test]
+    fn quantities_with_preferred_units_test() {
+        let data = parse_forecast(SAMPLE).unwrap();
+        let area = &data.forecast.unwrap().areas[0];
+        let units = Units::imperial();
+        let t = area.parameter("t").unwrap().quantities(&units);
+        let ws = area.parameter("ws").unwrap().quantities(&Units::metric());
+        let wd = area.parameter("wd").unwrap().quantities(&units);
+        let hu = area.parameter("hu").unwrap().quantities(&units);
+
+        assert_eq!(t[0].1, Quantity::new(80.6, Unit::Fahrenheit));
+        assert_eq!(ws[0].1, Quantity::new(9.26, Unit::KilometresPerHour));
+        assert_eq!(wd[0].1.to_string(), "SW");
+        assert_eq!(hu[0].1.unit, Unit::Percent);
+        assert!(area
+            .parameter("weather")
+            .unwrap()
+            .quantities(&units)
+            .is_empty());
+    }
+}
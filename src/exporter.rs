@@ -0,0 +1,466 @@
+//! Prometheus exporter, enabled by the `exporter` feature.
+//!
+//! [`Exporter`] polls the earthquake feeds and a set of provinces on an
+//! interval and serves the collected [`Metrics`] at `/metrics` in the
+//! Prometheus text exposition format.
+
+use crate::cuaca::units::Units;
+use crate::cuaca::{self, Area, Data, Province};
+use crate::gempa::{self, Gempa, Url};
+use crate::Error;
+use axum::http::header;
+use axum::routing::get;
+use axum::Router;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Debug, Clone, Default)]
+struct FeedStats {
+    latency: Option<Duration>,
+    errors: u64,
+    last_success: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+struct AreaSample {
+    id: String,
+    name: String,
+    temperature: Option<f64>,
+    humidity: Option<f64>,
+    wind_speed: Option<f64>,
+}
+
+/// Latest observations of every polled feed.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    feeds: BTreeMap<String, FeedStats>,
+    latest_quake: Option<Gempa>,
+    terkini_count: Option<usize>,
+    issues: BTreeMap<String, DateTime<Utc>>,
+    areas: BTreeMap<String, Vec<AreaSample>>,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let inner = pairs
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape(v)))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{{{}}}", inner)
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Value of the time range in effect at `now`: the latest one starting at
+/// or before it, otherwise the first one. Units are the crate defaults
+/// (Celsius, percent, knots).
+fn current(area: &Area, id: &str, now: DateTime<Utc>) -> Option<f64> {
+    let series = area.parameter(id)?.quantities(&Units::default());
+    series
+        .iter()
+        .rfind(|(d, _)| *d <= now)
+        .or_else(|| series.first())
+        .map(|(_, q)| q.value)
+}
+
+type AreaGauge = (&'static str, &'static str, fn(&AreaSample) -> Option<f64>);
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn feed(&mut self, name: &str, latency: Duration, ok: bool, now: DateTime<Utc>) {
+        let stats = self.feeds.entry(name.to_string()).or_default();
+        stats.latency = Some(latency);
+        if ok {
+            stats.last_success = Some(now);
+        } else {
+            stats.errors += 1;
+        }
+    }
+
+    pub fn record_gempa(
+        &mut self,
+        url: Url,
+        result: &Result<Vec<Gempa>, Error>,
+        latency: Duration,
+        now: DateTime<Utc>,
+    ) {
+        self.feed(url.key(), latency, result.is_ok(), now);
+        let data = match result {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+
+        match url {
+            Url::Autogempa => self.latest_quake = data.first().cloned(),
+            Url::GempaTerkini => self.terkini_count = Some(data.len()),
+            Url::GempaDirasakan => (),
+        }
+    }
+
+    pub fn record_cuaca(
+        &mut self,
+        p: Province,
+        result: &Result<Data, Error>,
+        latency: Duration,
+        now: DateTime<Utc>,
+    ) {
+        self.feed(&format!("cuaca_{}", p.key()), latency, result.is_ok(), now);
+        let data = match result {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        let forecast = match data.forecast {
+            Some(ref f) => f,
+            None => return,
+        };
+
+        if let Some(issued) = forecast.issue.as_ref().and_then(|i| i.datetime_utc()) {
+            self.issues.insert(p.key().to_string(), issued);
+        }
+        let samples = forecast
+            .areas
+            .iter()
+            .map(|a| AreaSample {
+                id: a.id.clone().unwrap_or_default(),
+                name: a.description.clone().unwrap_or_default(),
+                temperature: current(a, "t", now),
+                humidity: current(a, "hu", now),
+                wind_speed: current(a, "ws", now),
+            })
+            .collect();
+        self.areas.insert(p.key().to_string(), samples);
+    }
+
+    /// Renders the text exposition format, ages relative to `now`.
+    pub fn render(&self, now: DateTime<Utc>) -> String {
+        let mut out = String::new();
+
+        if let Some(ref g) = self.latest_quake {
+            let gauges = [
+                (
+                    "bmkgw_gempa_latest_magnitude",
+                    "Magnitude of the latest earthquake.",
+                    g.mag(),
+                ),
+                (
+                    "bmkgw_gempa_latest_depth_km",
+                    "Depth of the latest earthquake in km.",
+                    g.depth_km(),
+                ),
+                (
+                    "bmkgw_gempa_latest_timestamp_seconds",
+                    "Origin time of the latest earthquake as a Unix timestamp.",
+                    g.time().map(|t| t.timestamp() as f64),
+                ),
+            ];
+            for (name, help, value) in gauges.iter() {
+                if let Some(value) = value {
+                    header(&mut out, name, "gauge", help);
+                    let _ = writeln!(out, "{} {}", name, value);
+                }
+            }
+        }
+        if let Some(count) = self.terkini_count {
+            header(
+                &mut out,
+                "bmkgw_gempa_terkini_count",
+                "gauge",
+                "Earthquakes in the gempaterkini feed.",
+            );
+            let _ = writeln!(out, "bmkgw_gempa_terkini_count {}", count);
+        }
+
+        if !self.issues.is_empty() {
+            header(
+                &mut out,
+                "bmkgw_cuaca_issue_timestamp_seconds",
+                "gauge",
+                "Issue time of the forecast as a Unix timestamp.",
+            );
+            for (province, issued) in self.issues.iter() {
+                let _ = writeln!(
+                    out,
+                    "bmkgw_cuaca_issue_timestamp_seconds{} {}",
+                    labels(&[("province", province)]),
+                    issued.timestamp()
+                );
+            }
+        }
+
+        let area_gauges: [AreaGauge; 3] = [
+            (
+                "bmkgw_cuaca_temperature_celsius",
+                "Forecast temperature in effect.",
+                |a| a.temperature,
+            ),
+            (
+                "bmkgw_cuaca_humidity_percent",
+                "Forecast relative humidity in effect.",
+                |a| a.humidity,
+            ),
+            (
+                "bmkgw_cuaca_wind_speed_knots",
+                "Forecast wind speed in effect.",
+                |a| a.wind_speed,
+            ),
+        ];
+        for (name, help, value) in area_gauges.iter() {
+            let mut lines = Vec::new();
+            for (province, areas) in self.areas.iter() {
+                for area in areas {
+                    if let Some(v) = value(area) {
+                        let l = labels(&[
+                            ("province", province),
+                            ("area_id", &area.id),
+                            ("area", &area.name),
+                        ]);
+                        lines.push(format!("{}{} {}", name, l, v));
+                    }
+                }
+            }
+            if !lines.is_empty() {
+                header(&mut out, name, "gauge", help);
+                for line in lines {
+                    let _ = writeln!(out, "{}", line);
+                }
+            }
+        }
+
+        if !self.feeds.is_empty() {
+            header(
+                &mut out,
+                "bmkgw_fetch_duration_seconds",
+                "gauge",
+                "Duration of the last fetch.",
+            );
+            for (feed, stats) in self.feeds.iter() {
+                if let Some(latency) = stats.latency {
+                    let _ = writeln!(
+                        out,
+                        "bmkgw_fetch_duration_seconds{} {}",
+                        labels(&[("feed", feed)]),
+                        latency.as_secs_f64()
+                    );
+                }
+            }
+            header(
+                &mut out,
+                "bmkgw_fetch_errors_total",
+                "counter",
+                "Failed fetches.",
+            );
+            for (feed, stats) in self.feeds.iter() {
+                let _ = writeln!(
+                    out,
+                    "bmkgw_fetch_errors_total{} {}",
+                    labels(&[("feed", feed)]),
+                    stats.errors
+                );
+            }
+            header(
+                &mut out,
+                "bmkgw_data_age_seconds",
+                "gauge",
+                "Time since the last successful fetch.",
+            );
+            for (feed, stats) in self.feeds.iter() {
+                if let Some(last) = stats.last_success {
+                    let age = (now - last).num_milliseconds() as f64 / 1000.0;
+                    let _ = writeln!(
+                        out,
+                        "bmkgw_data_age_seconds{} {}",
+                        labels(&[("feed", feed)]),
+                        age
+                    );
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Polls feeds into shared [`Metrics`] and serves them.
+pub struct Exporter {
+    provinces: Vec<Province>,
+    interval: Duration,
+    metrics: Arc<Mutex<Metrics>>,
+}
+
+impl Exporter {
+    pub fn new(provinces: Vec<Province>, interval: Duration) -> Self {
+        Self {
+            provinces,
+            interval,
+            metrics: Arc::new(Mutex::new(Metrics::new())),
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<Mutex<Metrics>> {
+        self.metrics.clone()
+    }
+
+    /// Fetches every feed once.
+    pub async fn poll(&self) {
+        for url in [Url::Autogempa, Url::GempaTerkini, Url::GempaDirasakan] {
+            let started = Instant::now();
+            let result = gempa::get_data(url).await;
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.record_gempa(url, &result, started.elapsed(), Utc::now());
+        }
+        for p in self.provinces.iter() {
+            let started = Instant::now();
+            let result = cuaca::get_data(*p).await;
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.record_cuaca(*p, &result, started.elapsed(), Utc::now());
+        }
+    }
+
+    pub fn router(&self) -> Router {
+        let metrics = self.metrics.clone();
+        Router::new().route(
+            "/metrics",
+            get(move || async move {
+                let body = metrics.lock().unwrap().render(Utc::now());
+                ([(header::CONTENT_TYPE, CONTENT_TYPE)], body)
+            }),
+        )
+    }
+
+    /// Polls on the configured interval while serving `/metrics` on `addr`.
+    pub async fn run(self, addr: SocketAddr) -> Result<(), Error> {
+        let router = self.router();
+        let exporter = Arc::new(self);
+        let poller = exporter.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(poller.interval);
+            loop {
+                interval.tick().await;
+                poller.poll().await;
+            }
+        });
+
+        axum::Server::bind(&addr)
+            .serve(router.into_make_service())
+            .await
+            .map_err(|e| Error::Others(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast_file;
+    use crate::gempa::parse_earthquakes_file;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 10, 19, 7, 0, 0).unwrap()
+    }
+
+    fn metrics() -> Metrics {
+        let mut metrics = Metrics::new();
+        let latency = Duration::from_millis(250);
+        let auto = parse_earthquakes_file("fixtures/autogempa.xml");
+        let terkini = parse_earthquakes_file("fixtures/gempaterkini.xml");
+        let dki = parse_forecast_file("fixtures/DigitalForecast-Sample.xml");
+
+        metrics.record_gempa(Url::Autogempa, &auto, latency, now());
+        metrics.record_gempa(Url::GempaTerkini, &terkini, latency, now());
+        metrics.record_gempa(
+            Url::GempaDirasakan,
+            &Err(Error::Others("down".to_string())),
+            latency,
+            now(),
+        );
+        metrics.record_cuaca(Province::DKI, &dki, latency, now());
+        metrics
+    }
+
+    #[test]
+    fn render_gempa_test() {
+        let text = metrics().render(now() + chrono::Duration::seconds(30));
+
+        assert!(text.contains(
+            "# TYPE bmkgw_gempa_latest_magnitude gauge\nbmkgw_gempa_latest_magnitude 5.2\n"
+        ));
+        assert!(text.contains("bmkgw_gempa_latest_depth_km 10\n"));
+        assert!(text.contains("bmkgw_gempa_latest_timestamp_seconds 1634598944\n"));
+        assert!(text.contains("bmkgw_gempa_terkini_count 8\n"));
+    }
+
+    #[test]
+    fn render_cuaca_test() {
+        let text = metrics().render(now());
+
+        // 07:00 UTC falls in the 06:00 time range
+        assert!(text.contains(
+            "bmkgw_cuaca_temperature_celsius{province=\"dki\",area_id=\"501195\",area=\"Jakarta Pusat\"} 31\n"
+        ));
+        assert!(text.contains("bmkgw_cuaca_humidity_percent{province=\"dki\",area_id=\"501195\",area=\"Jakarta Pusat\"} 65\n"));
+        assert!(text.contains("bmkgw_cuaca_wind_speed_knots{province=\"dki\",area_id=\"5002220\",area=\"Kepulauan Seribu\"} 12\n"));
+        assert!(text.contains("bmkgw_cuaca_issue_timestamp_seconds{province=\"dki\"} 1634616731\n"));
+    }
+
+    #[test]
+    fn render_fetch_stats_test() {
+        let text = metrics().render(now() + chrono::Duration::seconds(30));
+
+        assert!(text.contains("bmkgw_fetch_duration_seconds{feed=\"autogempa\"} 0.25\n"));
+        assert!(text.contains("bmkgw_fetch_errors_total{feed=\"gempadirasakan\"} 1\n"));
+        assert!(text.contains("bmkgw_fetch_errors_total{feed=\"autogempa\"} 0\n"));
+        assert!(text.contains("bmkgw_data_age_seconds{feed=\"cuaca_dki\"} 30\n"));
+        assert!(!text.contains("bmkgw_data_age_seconds{feed=\"gempadirasakan\"}"));
+    }
+
+    #[test]
+    fn escape_test() {
+        assert_eq!(
+            labels(&[("area", "a \"b\"\\c")]),
+            "{area=\"a \\\"b\\\"\\\\c\"}"
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_route_test() {
+        let exporter = Exporter::new(vec![Province::DKI], Duration::from_secs(60));
+        *exporter.metrics().lock().unwrap() = metrics();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(exporter.router().into_make_service());
+        tokio::spawn(server);
+
+        let res = reqwest::get(&format!("http://{}/metrics", addr))
+            .await
+            .unwrap();
+        let content_type = res.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = res.text().await.unwrap();
+
+        assert_eq!(content_type, CONTENT_TYPE);
+        assert!(body.contains("bmkgw_gempa_terkini_count 8"));
+    }
+}
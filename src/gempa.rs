@@ -1,11 +1,31 @@
-use crate::{Error, BMKG_BASE_URL};
+use crate::{geo, Error, TimeZone, BMKG_BASE_URL};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone as _, Utc};
 use quick_xml::events::Event;
 use quick_xml::Reader;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use std::borrow::Borrow;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+pub mod alert;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod atom;
+pub mod cap;
+#[cfg(feature = "sqlite")]
+pub mod catalog;
+pub mod cluster;
+pub mod csv;
+pub mod energy;
+pub mod intensity;
+pub mod merge;
+pub mod quakeml;
+pub mod stats;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Url {
     Autogempa,
     GempaTerkini,
@@ -13,6 +33,14 @@ pub enum Url {
 }
 
 impl Url {
+    /// Key used by `from_str`, e.g. "gempaterkini".
+    pub fn key(&self) -> &'static str {
+        match self {
+            Url::Autogempa => "autogempa",
+            Url::GempaTerkini => "gempaterkini",
+            Url::GempaDirasakan => "gempadirasakan",
+        }
+    }
     pub fn to_str(&self) -> String {
         match self {
             Url::Autogempa => format!("{}/DataMKG/TEWS/autogempa.xml", BMKG_BASE_URL),
@@ -20,6 +48,7 @@ impl Url {
             Url::GempaDirasakan => format!("{}/DataMKG/TEWS/gempadirasakan.xml", BMKG_BASE_URL),
         }
     }
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str<T: Borrow<str>>(s: T) -> Option<Url> {
         match s.borrow().to_lowercase().as_ref() {
             "autogempa" => Some(Url::Autogempa),
@@ -31,9 +60,11 @@ impl Url {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct Gempa {
     pub tanggal: Option<String>,
     pub jam: Option<String>,
+    pub datetime: Option<String>,
     pub coordinates: Option<String>,
     pub lintang: Option<String>,
     pub bujur: Option<String>,
@@ -50,6 +81,7 @@ impl Gempa {
         Self {
             tanggal: None,
             jam: None,
+            datetime: None,
             coordinates: None,
             lintang: None,
             bujur: None,
@@ -72,6 +104,10 @@ impl Gempa {
                 self.jam = Some(v);
                 Ok(())
             }
+            "datetime" => {
+                self.datetime = Some(v);
+                Ok(())
+            }
             "coordinates" => {
                 self.coordinates = Some(v);
                 Ok(())
@@ -114,13 +150,147 @@ impl Gempa {
             }
         }
     }
+    /// Origin time, from `DateTime` when present, otherwise from `Tanggal`
+    /// and `Jam` (e.g. "19 Okt 2021" or "30-Jul-20", "06:15:44 WIB").
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        if let Some(t) = self
+            .datetime
+            .as_ref()
+            .and_then(|x| DateTime::parse_from_rfc3339(x.trim()).ok())
+        {
+            return Some(t.with_timezone(&Utc));
+        }
+
+        let date = parse_tanggal(self.tanggal.as_ref()?)?;
+        let mut jam = self.jam.as_ref()?.split_whitespace();
+        let time = NaiveTime::parse_from_str(jam.next()?, "%H:%M:%S").ok()?;
+        let tz = jam
+            .next()
+            .and_then(TimeZone::from_str)
+            .unwrap_or(TimeZone::WIB);
+
+        tz.utc_offset()
+            .from_local_datetime(&date.and_time(time))
+            .single()
+            .map(|t| t.with_timezone(&Utc))
+    }
+    /// Stable identifier of the event: its UTC origin time as `YYYYMMDDhhmmss`.
+    pub fn event_key(&self) -> Option<String> {
+        self.time().map(|t| t.format("%Y%m%d%H%M%S").to_string())
+    }
+    /// Magnitude, `None` when missing or not a finite number.
+    pub fn mag(&self) -> Option<f64> {
+        let mag: f64 = self.magnitude.as_ref()?.trim().parse().ok()?;
+        Some(mag).filter(|m| m.is_finite())
+    }
+    /// Depth in km, from e.g. "10 km".
+    pub fn depth_km(&self) -> Option<f64> {
+        let depth = self.kedalaman.as_ref()?;
+        let depth: f64 = depth.split_whitespace().next()?.parse().ok()?;
+        Some(depth).filter(|d| d.is_finite())
+    }
+    /// Latitude in decimal degrees, from `coordinates` ("lat,lon") or `Lintang` ("8.88 LS").
+    pub fn latitude(&self) -> Option<f64> {
+        self.coordinate(0)
+            .or_else(|| parse_hemisphere(self.lintang.as_ref()?, "LS"))
+    }
+    /// Longitude in decimal degrees, from `coordinates` ("lat,lon") or `Bujur` ("121.95 BT").
+    pub fn longitude(&self) -> Option<f64> {
+        self.coordinate(1)
+            .or_else(|| parse_hemisphere(self.bujur.as_ref()?, "BB"))
+    }
+    fn coordinate(&self, idx: usize) -> Option<f64> {
+        let coordinates = self.coordinates.as_ref()?;
+        coordinates.split(',').nth(idx)?.trim().parse().ok()
+    }
+    /// Distance in km from the epicenter.
+    pub fn distance_km(&self, latitude: f64, longitude: f64) -> Option<f64> {
+        Some(geo::distance_km(
+            self.latitude()?,
+            self.longitude()?,
+            latitude,
+            longitude,
+        ))
+    }
+    /// Whether `Potensi` states a tsunami potential, `None` when it says neither.
+    pub fn tsunami_potential(&self) -> Option<bool> {
+        let potensi = self.potensi.as_ref()?.to_lowercase();
+        if potensi.contains("tidak berpotensi") {
+            Some(false)
+        } else if potensi.contains("berpotensi tsunami") {
+            Some(true)
+        } else {
+            None
+        }
+    }
+    /// Highest MMI in `Dirasakan`, e.g. 3 for "III Sikka, II - III Ende".
+    pub fn felt_intensity(&self) -> Option<u8> {
+        self.dirasakan
+            .as_ref()?
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .filter_map(roman_to_mmi)
+            .max()
+    }
     pub fn to_json(self) -> JsonValue {
         json!(self)
     }
 }
 
-fn parse_data<T: Borrow<str>>(xml: T) -> Result<Vec<Gempa>, Error> {
-    let mut reader = Reader::from_str(xml.borrow());
+const MMI_SCALE: [&str; 12] = [
+    "I", "II", "III", "IV", "V", "VI", "VII", "VIII", "IX", "X", "XI", "XII",
+];
+
+fn roman_to_mmi(s: &str) -> Option<u8> {
+    MMI_SCALE.iter().position(|x| *x == s).map(|i| i as u8 + 1)
+}
+
+fn parse_hemisphere(s: &str, negative: &str) -> Option<f64> {
+    let mut parts = s.split_whitespace();
+    let value: f64 = parts.next()?.parse().ok()?;
+    match parts.next() {
+        Some(h) if h.eq_ignore_ascii_case(negative) => Some(-value),
+        _ => Some(value),
+    }
+}
+
+fn month_number(s: &str) -> Option<u32> {
+    let month = match s.to_lowercase().get(..3)? {
+        "jan" => 1,
+        "feb" => 2,
+        "mar" => 3,
+        "apr" => 4,
+        "mei" | "may" => 5,
+        "jun" => 6,
+        "jul" => 7,
+        "agu" | "agt" | "ags" | "aug" => 8,
+        "sep" => 9,
+        "okt" | "oct" => 10,
+        "nov" => 11,
+        "des" | "dec" => 12,
+        _ => return None,
+    };
+    Some(month)
+}
+
+fn parse_tanggal(s: &str) -> Option<NaiveDate> {
+    let parts = s
+        .split(|c: char| c == '-' || c.is_whitespace())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    if parts.len() != 3 {
+        return None;
+    }
+
+    let day = parts[0].parse().ok()?;
+    let month = month_number(parts[1])?;
+    let year: i32 = parts[2].parse().ok()?;
+    let year = if year < 100 { 2000 + year } else { year };
+
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+fn parse_data<B: BufRead>(inner: B) -> Result<Vec<Gempa>, Error> {
+    let mut reader = Reader::from_reader(inner);
     reader.trim_text(true);
 
     let mut buf = Vec::new();
@@ -130,29 +300,25 @@ fn parse_data<T: Borrow<str>>(xml: T) -> Result<Vec<Gempa>, Error> {
     loop {
         match reader.read_event(&mut buf) {
             Ok(Event::Start(ref e)) => match e.name() {
-                b"Tanggal" | b"Jam" | b"coordinates" | b"Lintang" | b"Bujur" | b"Magnitude"
-                | b"Kedalaman" | b"Wilayah" | b"Potensi" | b"Dirasakan" | b"Shakemap" => {
+                b"Tanggal" | b"Jam" | b"DateTime" | b"coordinates" | b"Lintang" | b"Bujur"
+                | b"Magnitude" | b"Kedalaman" | b"Wilayah" | b"Potensi" | b"Dirasakan"
+                | b"Shakemap" => {
                     let mut text = reader.read_text(e.name(), &mut Vec::new())?;
                     let v = e.unescape_and_decode(&reader)?;
                     if v == "Shakemap" {
                         text = format!("{}/DataMKG/TEWS/{}", BMKG_BASE_URL, text);
                     }
-                    match g.set(v, text.clone()) {
-                        _ => (),
-                    };
-                }
-                _ => (),
-            },
-            Ok(Event::End(ref e)) => match e.name() {
-                b"gempa" => {
-                    res = res.and_then(|mut x| {
-                        x.push(g);
-                        Ok(x)
-                    });
-                    g = Gempa::new();
+                    let _ = g.set(v, text);
                 }
                 _ => (),
             },
+            Ok(Event::End(ref e)) if e.name() == b"gempa" => {
+                res = res.map(|mut x| {
+                    x.push(g);
+                    x
+                });
+                g = Gempa::new();
+            }
             Ok(Event::Eof) => break, // exits the loop when reaching end of file
             Err(e) => {
                 res = Err(Error::XmlError(e));
@@ -168,9 +334,23 @@ fn parse_data<T: Borrow<str>>(xml: T) -> Result<Vec<Gempa>, Error> {
     res
 }
 
+/// Parses an earthquake document held in memory, e.g. a `&str` or `Vec<u8>`.
+pub fn parse_earthquakes<T: AsRef<[u8]>>(xml: T) -> Result<Vec<Gempa>, Error> {
+    parse_data(xml.as_ref())
+}
+
+pub fn parse_earthquakes_reader<R: Read>(r: R) -> Result<Vec<Gempa>, Error> {
+    parse_data(BufReader::new(r))
+}
+
+pub fn parse_earthquakes_file<P: AsRef<Path>>(path: P) -> Result<Vec<Gempa>, Error> {
+    let file = File::open(path)?;
+    parse_earthquakes_reader(file)
+}
+
 pub async fn get_data(url: Url) -> Result<Vec<Gempa>, Error> {
-    let xml = reqwest::get(&url.to_str()).await?.text().await?;
-    parse_data(xml)
+    let xml = reqwest::get(&url.to_str()).await?.bytes().await?;
+    parse_earthquakes(xml)
 }
 
 #[cfg(test)]
@@ -186,19 +366,120 @@ mod tests {
         assert_eq!(data.tanggal, expected);
     }
 
+    #[test]
+    fn gempa_time_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let mut g = data[1].clone();
+        let expected = "2021-10-18T23:15:44+00:00";
+
+        assert_eq!(g.time().unwrap().to_rfc3339(), expected);
+
+        g.datetime = None;
+        assert_eq!(g.time().unwrap().to_rfc3339(), expected);
+
+        g.tanggal = Some("30-Jul-20".to_string());
+        g.jam = Some("09:51:20 WITA".to_string());
+        assert_eq!(g.time().unwrap().to_rfc3339(), "2020-07-30T01:51:20+00:00");
+    }
+
+    #[test]
+    fn gempa_event_key_test() {
+        let terkini = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let auto = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+
+        assert_eq!(auto[0].event_key(), Some("20211018231544".to_string()));
+        assert_eq!(auto[0].event_key(), terkini[1].event_key());
+        assert_eq!(Gempa::new().event_key(), None);
+    }
+
+    #[test]
+    fn gempa_numeric_fields_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let mut g = data[3].clone();
+
+        assert_eq!(g.mag(), Some(5.6));
+        assert_eq!(g.depth_km(), Some(40.0));
+
+        let mut bogus = g.clone();
+        bogus.magnitude = Some("NaN".to_string());
+        bogus.kedalaman = Some("inf km".to_string());
+        assert_eq!(bogus.mag(), None);
+        assert_eq!(bogus.depth_km(), None);
+        assert_eq!(g.latitude(), Some(2.1));
+        assert_eq!(g.longitude(), Some(126.8));
+
+        g.coordinates = None;
+        g.lintang = Some("8.88 LS".to_string());
+        assert_eq!(g.latitude(), Some(-8.88));
+        assert_eq!(g.longitude(), Some(126.8));
+    }
+
+    #[test]
+    fn gempa_tsunami_and_felt_test() {
+        let terkini = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let dirasakan = parse_earthquakes_file("fixtures/gempadirasakan.xml").unwrap();
+
+        assert_eq!(terkini[0].tsunami_potential(), Some(false));
+        assert_eq!(terkini[2].tsunami_potential(), Some(true));
+        assert_eq!(dirasakan[0].tsunami_potential(), None);
+        assert_eq!(dirasakan[0].felt_intensity(), Some(3));
+        assert_eq!(dirasakan[2].felt_intensity(), Some(4));
+        assert_eq!(terkini[0].felt_intensity(), None);
+    }
+
+    #[test]
+    fn gempa_distance_test() {
+        let data = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+        // Maumere
+        let d = data[0].distance_km(-8.62, 122.21).unwrap();
+
+        assert!((d - 40.0).abs() < 2.0);
+    }
+
     #[test]
     fn parse_data_test() {
         let data = "<gempa><Tanggal>30-Jul-20</Tanggal><Jam>09:51:20 WIB</Jam></gempa>";
         let expected = Some("30-Jul-20".to_string());
 
-        assert_eq!(parse_data(data).unwrap()[0].tanggal, expected);
+        assert_eq!(parse_data(data.as_bytes()).unwrap()[0].tanggal, expected);
     }
 
     #[test]
     fn parse_data_without_gempa_tag_test() {
         let data = "<Tanggal>30-Jul-20</Tanggal><Jam>09:51:20 WIB</Jam>";
 
-        assert_eq!(parse_data(data).unwrap().len(), 0);
+        assert_eq!(parse_data(data.as_bytes()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn parse_earthquakes_test() {
+        let xml = include_str!("../fixtures/gempaterkini.xml");
+        let from_str = parse_earthquakes(xml).unwrap();
+        let from_reader = parse_earthquakes_reader(xml.as_bytes()).unwrap();
+
+        assert_eq!(from_str.len(), 8);
+        assert_eq!(from_reader.len(), 8);
+        assert_eq!(from_str[1].magnitude, Some("5.2".to_string()));
+        assert_eq!(from_str[1].coordinates, Some("-8.88,121.95".to_string()));
+    }
+
+    #[test]
+    fn parse_earthquakes_file_test() {
+        let data = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+        let expected = Some(format!(
+            "{}/DataMKG/TEWS/{}",
+            BMKG_BASE_URL, "20211019061544.mmi.jpg"
+        ));
+
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].shakemap, expected);
+    }
+
+    #[test]
+    fn parse_earthquakes_file_not_found_test() {
+        let res = parse_earthquakes_file("fixtures/does-not-exist.xml");
+
+        assert!(matches!(res, Err(Error::IoError(_))));
     }
 
     #[tokio::test]
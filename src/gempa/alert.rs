@@ -0,0 +1,357 @@
+use crate::gempa::Gempa;
+use crate::{read_config, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Minor,
+    Moderate,
+    Severe,
+    Extreme,
+}
+
+/// A named location rules can measure epicentral distance from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Site {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Conditions on a single earthquake. All given conditions must hold; an
+/// event lacking a value a condition needs does not match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: String,
+    pub description: Option<String>,
+    pub severity: Severity,
+    pub min_magnitude: Option<f64>,
+    pub max_magnitude: Option<f64>,
+    pub min_depth_km: Option<f64>,
+    pub max_depth_km: Option<f64>,
+    /// Site name the distance is measured from; any site when missing.
+    pub site: Option<String>,
+    pub max_distance_km: Option<f64>,
+    pub tsunami: Option<bool>,
+    pub min_felt_intensity: Option<u8>,
+}
+
+fn at_least(value: Option<f64>, min: Option<f64>) -> bool {
+    match (value, min) {
+        (_, None) => true,
+        (Some(v), Some(m)) => v >= m,
+        (None, Some(_)) => false,
+    }
+}
+
+fn at_most(value: Option<f64>, max: Option<f64>) -> bool {
+    match (value, max) {
+        (_, None) => true,
+        (Some(v), Some(m)) => v <= m,
+        (None, Some(_)) => false,
+    }
+}
+
+impl Rule {
+    /// Nearest qualifying site and its distance. `Some(None)` when the rule
+    /// has no distance condition, `None` when no site qualifies.
+    fn site_distance(&self, g: &Gempa, sites: &[Site]) -> Option<Option<(String, f64)>> {
+        if self.site.is_none() && self.max_distance_km.is_none() {
+            return Some(None);
+        }
+
+        let nearest = sites
+            .iter()
+            .filter(|s| self.site.as_ref().is_none_or(|name| *name == s.name))
+            .filter_map(|s| Some((s.name.clone(), g.distance_km(s.latitude, s.longitude)?)))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))?;
+
+        if at_most(Some(nearest.1), self.max_distance_km) {
+            Some(Some(nearest))
+        } else {
+            None
+        }
+    }
+
+    pub fn evaluate(&self, g: &Gempa, sites: &[Site]) -> Option<Match> {
+        let magnitude = g.mag();
+        let depth = g.depth_km();
+        let matches = at_least(magnitude, self.min_magnitude)
+            && at_most(magnitude, self.max_magnitude)
+            && at_least(depth, self.min_depth_km)
+            && at_most(depth, self.max_depth_km)
+            && self
+                .tsunami
+                .is_none_or(|t| g.tsunami_potential() == Some(t))
+            && at_least(
+                g.felt_intensity().map(f64::from),
+                self.min_felt_intensity.map(f64::from),
+            );
+        if !matches {
+            return None;
+        }
+
+        let site = self.site_distance(g, sites)?;
+        Some(Match {
+            rule_id: self.id.clone(),
+            severity: self.severity,
+            site: site.as_ref().map(|(name, _)| name.clone()),
+            distance_km: site.map(|(_, d)| d),
+            gempa: g.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Match {
+    pub rule_id: String,
+    pub severity: Severity,
+    pub site: Option<String>,
+    pub distance_km: Option<f64>,
+    pub gempa: Gempa,
+}
+
+impl Match {
+    pub fn to_json(self) -> JsonValue {
+        json!(self)
+    }
+}
+
+/// Sites and rules as loaded from a TOML (`[[sites]]`, `[[rules]]`) or JSON file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleSet {
+    #[serde(default)]
+    pub sites: Vec<Site>,
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn from_toml(s: &str) -> Result<Self, Error> {
+        toml::from_str::<Self>(s)?.validate()
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, Error> {
+        serde_json::from_str::<Self>(s)?.validate()
+    }
+
+    /// Loads a `.toml` or `.json` file, chosen by extension.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        read_config::<Self, _>(path)?.validate()
+    }
+
+    /// Rejects rules naming a site missing from `sites`, rules with a site
+    /// but no `max_distance_km`, and distances that are negative or not
+    /// finite. Such rules would otherwise never fire, or fire everywhere.
+    fn validate(self) -> Result<Self, Error> {
+        for rule in self.rules.iter() {
+            if let Some(ref site) = rule.site {
+                if !self.sites.iter().any(|s| s.name == *site) {
+                    let msg = format!("rule {}: unknown site {}", rule.id, site);
+                    return Err(Error::Others(msg));
+                }
+            }
+            match rule.max_distance_km {
+                Some(d) if !d.is_finite() || d < 0.0 => {
+                    let msg = format!("rule {}: invalid max_distance_km {}", rule.id, d);
+                    return Err(Error::Others(msg));
+                }
+                None if rule.site.is_some() => {
+                    let msg = format!("rule {}: site without max_distance_km", rule.id);
+                    return Err(Error::Others(msg));
+                }
+                _ => (),
+            }
+        }
+        Ok(self)
+    }
+
+    /// Matches of every rule, most severe first.
+    pub fn evaluate(&self, g: &Gempa) -> Vec<Match> {
+        let mut matches = self
+            .rules
+            .iter()
+            .filter_map(|r| r.evaluate(g, &self.sites))
+            .collect::<Vec<_>>();
+        matches.sort_by_key(|m| Reverse(m.severity));
+
+        matches
+    }
+}
+
+/// A [`RuleSet`] backed by a file that is re-read when its modification
+/// time changes.
+///
+/// A file that fails to load or validate leaves the previous rules in place.
+#[derive(Debug)]
+pub struct RuleWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    rules: RuleSet,
+}
+
+fn modified<P: AsRef<Path>>(path: P) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl RuleWatcher {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified(&path);
+        let rules = RuleSet::from_file(&path)?;
+
+        Ok(Self {
+            path,
+            modified,
+            rules,
+        })
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// Reloads the file if it changed since the last load, returns whether it did.
+    pub fn reload_if_changed(&mut self) -> Result<bool, Error> {
+        let current = modified(&self.path);
+        if current == self.modified {
+            return Ok(false);
+        }
+
+        let rules = RuleSet::from_file(&self.path)?;
+        self.rules = rules;
+        self.modified = current;
+
+        Ok(true)
+    }
+
+    pub fn evaluate(&self, g: &Gempa) -> Vec<Match> {
+        self.rules.evaluate(g)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+    use std::time::Duration;
+
+    fn rule_set() -> RuleSet {
+        RuleSet::from_file("fixtures/gempa_rules.toml").unwrap()
+    }
+
+    fn ids(matches: &[Match]) -> Vec<&str> {
+        matches.iter().map(|m| m.rule_id.as_str()).collect()
+    }
+
+    #[test]
+    fn rule_set_from_file_test() {
+        let rules = rule_set();
+
+        assert_eq!(rules.sites.len(), 2);
+        assert_eq!(rules.rules.len(), 4);
+        assert_eq!(rules.rules[1].severity, Severity::Severe);
+    }
+
+    #[test]
+    fn rule_set_validate_test() {
+        let rules = |rule: &str| {
+            format!(
+                "[[sites]]\nname = \"depot\"\nlatitude = -8.6\nlongitude = 122.2\n\n\
+                 [[rules]]\nid = \"r\"\nseverity = \"info\"\n{}",
+                rule
+            )
+        };
+
+        assert!(RuleSet::from_toml(&rules("site = \"depot\"\nmax_distance_km = 50")).is_ok());
+        assert!(RuleSet::from_toml(&rules("site = \"depto\"\nmax_distance_km = 50")).is_err());
+        assert!(RuleSet::from_toml(&rules("site = \"depot\"")).is_err());
+        assert!(RuleSet::from_toml(&rules("max_distance_km = -1")).is_err());
+        assert!(RuleSet::from_json(
+            r#"{"rules": [{"id": "r", "severity": "info", "site": "depot", "max_distance_km": 5}]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn evaluate_near_site_test() {
+        let g = &parse_earthquakes_file("fixtures/autogempa.xml").unwrap()[0];
+        let matches = rule_set().evaluate(g);
+
+        assert_eq!(ids(&matches), vec!["near-depot", "near-any-site"]);
+        assert_eq!(matches[0].site, Some("maumere-depot".to_string()));
+        assert!((matches[0].distance_km.unwrap() - 40.0).abs() < 2.0);
+    }
+
+    #[test]
+    fn evaluate_tsunami_test() {
+        let g = &parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap()[2];
+        let matches = rule_set().evaluate(g);
+
+        assert_eq!(ids(&matches), vec!["tsunami", "near-any-site"]);
+        assert_eq!(matches[0].severity, Severity::Extreme);
+        assert_eq!(matches[0].distance_km, None);
+        assert_eq!(matches[1].site, Some("padang-office".to_string()));
+    }
+
+    #[test]
+    fn evaluate_felt_test() {
+        let dirasakan = parse_earthquakes_file("fixtures/gempadirasakan.xml").unwrap();
+
+        assert_eq!(
+            ids(&rule_set().evaluate(&dirasakan[2])),
+            vec!["near-any-site", "strongly-felt"]
+        );
+        assert!(rule_set().evaluate(&dirasakan[4]).is_empty());
+    }
+
+    #[test]
+    fn evaluate_missing_value_test() {
+        let mut g = parse_earthquakes_file("fixtures/autogempa.xml").unwrap()[0].clone();
+        g.kedalaman = None;
+
+        assert_eq!(ids(&rule_set().evaluate(&g)), vec!["near-any-site"]);
+    }
+
+    #[test]
+    fn rule_watcher_reload_test() {
+        let path = std::env::temp_dir().join(format!("bmkgw-rules-{}.json", std::process::id()));
+        let rules = |id: &str| format!(r#"{{"rules": [{{"id": "{}", "severity": "info"}}]}}"#, id);
+        fs::write(&path, rules("first")).unwrap();
+
+        let mut watcher = RuleWatcher::open(&path).unwrap();
+        assert!(!watcher.reload_if_changed().unwrap());
+        assert_eq!(watcher.rules().rules[0].id, "first");
+
+        fs::write(&path, rules("second")).unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+        assert!(watcher.reload_if_changed().unwrap());
+        assert_eq!(watcher.rules().rules[0].id, "second");
+
+        fs::write(&path, "{ not json").unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(2))
+            .unwrap();
+        assert!(watcher.reload_if_changed().is_err());
+        assert_eq!(watcher.rules().rules[0].id, "second");
+
+        fs::write(
+            &path,
+            r#"{"rules": [{"id": "third", "severity": "info", "max_distance_km": -5}]}"#,
+        )
+        .unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(3))
+            .unwrap();
+        assert!(watcher.reload_if_changed().is_err());
+        assert_eq!(watcher.rules().rules[0].id, "second");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+//! Arrow and Parquet export of earthquake lists, enabled by the `arrow` feature.
+
+use super::Gempa;
+use crate::Error;
+use ::arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, StringArray, TimestampSecondArray, UInt8Array,
+};
+use ::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use ::arrow::record_batch::RecordBatch;
+use ::parquet::arrow::ArrowWriter;
+use std::io::Write;
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+/// Converts `data` into a record batch with one row per earthquake.
+pub fn to_record_batch(data: &[Gempa]) -> Result<RecordBatch, Error> {
+    let timestamp = DataType::Timestamp(TimeUnit::Second, Some("UTC".into()));
+    let schema = Schema::new(vec![
+        Field::new("event_key", DataType::Utf8, true),
+        Field::new("time", timestamp, true),
+        Field::new("magnitude", DataType::Float64, true),
+        Field::new("depth_km", DataType::Float64, true),
+        Field::new("latitude", DataType::Float64, true),
+        Field::new("longitude", DataType::Float64, true),
+        Field::new("region", DataType::Utf8, true),
+        Field::new("tsunami_potential", DataType::Boolean, true),
+        Field::new("felt_intensity", DataType::UInt8, true),
+        Field::new("potensi", DataType::Utf8, true),
+        Field::new("dirasakan", DataType::Utf8, true),
+        Field::new("shakemap", DataType::Utf8, true),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter(data.iter().map(|g| g.event_key()))),
+        Arc::new(
+            TimestampSecondArray::from_iter(data.iter().map(|g| g.time().map(|t| t.timestamp())))
+                .with_timezone("UTC"),
+        ),
+        Arc::new(Float64Array::from_iter(data.iter().map(|g| g.mag()))),
+        Arc::new(Float64Array::from_iter(data.iter().map(|g| g.depth_km()))),
+        Arc::new(Float64Array::from_iter(data.iter().map(|g| g.latitude()))),
+        Arc::new(Float64Array::from_iter(data.iter().map(|g| g.longitude()))),
+        Arc::new(StringArray::from_iter(
+            data.iter().map(|g| g.wilayah.as_deref()),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            data.iter().map(|g| g.tsunami_potential()),
+        )),
+        Arc::new(UInt8Array::from_iter(
+            data.iter().map(|g| g.felt_intensity()),
+        )),
+        Arc::new(StringArray::from_iter(
+            data.iter().map(|g| g.potensi.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter(
+            data.iter().map(|g| g.dirasakan.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter(
+            data.iter().map(|g| g.shakemap.as_deref()),
+        )),
+    ];
+
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Writes `data` as a Parquet file to `w`.
+pub fn write_parquet<W: Write + Send>(data: &[Gempa], w: W) -> Result<(), Error> {
+    let batch = to_record_batch(data)?;
+    let mut writer = ArrowWriter::try_new(w, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+    use ::arrow::array::Array;
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::fs::File;
+
+    #[test]
+    fn to_record_batch_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let batch = to_record_batch(&data).unwrap();
+
+        assert_eq!(batch.num_rows(), 8);
+        let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+        let mag = column("magnitude");
+        let mag = mag.as_any().downcast_ref::<Float64Array>().unwrap();
+        let time = column("time");
+        let time = time
+            .as_any()
+            .downcast_ref::<TimestampSecondArray>()
+            .unwrap();
+        let tsunami = column("tsunami_potential");
+        let tsunami = tsunami.as_any().downcast_ref::<BooleanArray>().unwrap();
+
+        assert_eq!(mag.value(1), 5.2);
+        assert_eq!(time.value(1), 1634598944);
+        assert!(tsunami.value(2));
+        assert!(column("felt_intensity").is_null(0));
+    }
+
+    #[test]
+    fn write_parquet_test() {
+        let data = parse_earthquakes_file("fixtures/gempadirasakan.xml").unwrap();
+        let path = std::env::temp_dir().join(format!("bmkgw-gempa-{}.parquet", std::process::id()));
+        write_parquet(&data, File::create(&path).unwrap()).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<_> = reader.map(|b| b.unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+        assert_eq!(
+            batches[0]
+                .schema()
+                .field_with_name("felt_intensity")
+                .unwrap()
+                .data_type(),
+            &DataType::UInt8
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Atom (RFC 4287) feed of earthquakes.
+//!
+//! Entry ids are derived from [`Gempa::event_key`], so an event keeps its id
+//! across polls and feed readers do not show it twice.
+
+use super::Gempa;
+use crate::escape_xml;
+use chrono::{DateTime, Utc};
+
+const ENTRY_ID_PREFIX: &str = "urn:bmkgw:gempa:";
+
+/// Feed metadata.
+#[derive(Debug, Clone)]
+pub struct Feed {
+    /// Permanent IRI of the feed.
+    pub id: String,
+    pub title: String,
+    /// URL the feed is published at, for the `self` link.
+    pub link: Option<String>,
+}
+
+impl Feed {
+    pub fn new<T: Into<String>>(id: T, title: T) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            link: None,
+        }
+    }
+
+    /// Renders the feed with one entry per earthquake. Events without an
+    /// origin time are left out, as RFC 4287 requires an entry `id` and
+    /// `updated`. `updated` is the latest origin time, or `now` when there
+    /// is no event.
+    pub fn to_xml(&self, data: &[Gempa], now: DateTime<Utc>) -> String {
+        let updated = data.iter().filter_map(|g| g.time()).max().unwrap_or(now);
+
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:georss=\"http://www.georss.org/georss\">\n");
+        out.push_str(&format!("<id>{}</id>\n", escape_xml(self.id.as_str())));
+        out.push_str(&format!(
+            "<title>{}</title>\n",
+            escape_xml(self.title.as_str())
+        ));
+        out.push_str(&format!("<updated>{}</updated>\n", updated.to_rfc3339()));
+        if let Some(ref link) = self.link {
+            out.push_str(&format!(
+                "<link rel=\"self\" href=\"{}\"/>\n",
+                escape_xml(link.as_str())
+            ));
+        }
+        out.push_str("<author><name>BMKG</name></author>\n");
+        for entry in data.iter().filter_map(entry) {
+            out.push_str(&entry);
+        }
+        out.push_str("</feed>\n");
+        out
+    }
+}
+
+/// Stable entry id, `None` when the origin time is unknown.
+pub fn entry_id(g: &Gempa) -> Option<String> {
+    g.event_key().map(|k| format!("{}{}", ENTRY_ID_PREFIX, k))
+}
+
+/// Entry title, e.g. "M5.2 – 45 km BaratDaya KAB-SIKKA".
+pub fn entry_title(g: &Gempa) -> String {
+    let mag = g.magnitude.as_deref().unwrap_or("?").trim();
+    match g.wilayah {
+        Some(ref wilayah) => format!("M{} – {}", mag, wilayah.trim()),
+        None => format!("M{}", mag),
+    }
+}
+
+fn content(g: &Gempa) -> String {
+    let mut lines = Vec::new();
+    if let Some(depth) = g.depth_km() {
+        lines.push(format!("Depth: {} km", depth));
+    }
+    if let (Some(lat), Some(lon)) = (g.latitude(), g.longitude()) {
+        lines.push(format!("Coordinates: {}, {}", lat, lon));
+    }
+    if let Some(ref potensi) = g.potensi {
+        lines.push(format!("Tsunami potential: {}", potensi));
+    }
+    if let Some(ref dirasakan) = g.dirasakan {
+        lines.push(format!("Felt: {}", dirasakan));
+    }
+
+    let mut html: String = lines
+        .iter()
+        .map(|l| format!("<p>{}</p>", escape_xml(l.as_str())))
+        .collect();
+    if let Some(ref shakemap) = g.shakemap {
+        let shakemap = escape_xml(shakemap.as_str());
+        html.push_str(&format!(
+            "<p><a href=\"{0}\"><img src=\"{0}\" alt=\"Shakemap\"/></a></p>",
+            shakemap
+        ));
+    }
+    html
+}
+
+fn entry(g: &Gempa) -> Option<String> {
+    let time = g.time()?;
+    let mut out = String::from("<entry>\n");
+    out.push_str(&format!("<id>{}</id>\n", entry_id(g)?));
+    out.push_str(&format!("<title>{}</title>\n", escape_xml(entry_title(g))));
+    out.push_str(&format!("<updated>{}</updated>\n", time.to_rfc3339()));
+    out.push_str(&format!("<published>{}</published>\n", time.to_rfc3339()));
+    if let Some(ref shakemap) = g.shakemap {
+        out.push_str(&format!(
+            "<link rel=\"enclosure\" type=\"image/jpeg\" href=\"{}\"/>\n",
+            escape_xml(shakemap.as_str())
+        ));
+    }
+    if let (Some(lat), Some(lon)) = (g.latitude(), g.longitude()) {
+        out.push_str(&format!("<georss:point>{} {}</georss:point>\n", lat, lon));
+    }
+    out.push_str(&format!(
+        "<content type=\"html\">{}</content>\n",
+        escape_xml(content(g))
+    ));
+    out.push_str("</entry>\n");
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes;
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    fn feed() -> Feed {
+        let mut feed = Feed::new("urn:bmkgw:feed:gempaterkini", "Gempa Terkini");
+        feed.link = Some("https://example.com/gempa.atom".to_string());
+        feed
+    }
+
+    /// Text of every `tag` element, in order.
+    fn texts(xml: &str, tag: &[u8]) -> Vec<String> {
+        let mut reader = Reader::from_str(xml);
+        let mut buf = Vec::new();
+        let mut out = Vec::new();
+        loop {
+            match reader.read_event(&mut buf).unwrap() {
+                Event::Start(ref e) if e.name() == tag => {
+                    out.push(reader.read_text(tag, &mut Vec::new()).unwrap());
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+            buf.clear();
+        }
+        out
+    }
+
+    #[test]
+    fn to_xml_test() {
+        let data = parse_earthquakes(include_str!("../../fixtures/autogempa.xml")).unwrap();
+        let xml = feed().to_xml(&data, Utc::now());
+
+        assert_eq!(
+            texts(&xml, b"title"),
+            vec!["Gempa Terkini", "M5.2 – 45 km BaratDaya KAB-SIKKA"]
+        );
+        assert_eq!(
+            texts(&xml, b"id"),
+            vec![
+                "urn:bmkgw:feed:gempaterkini",
+                "urn:bmkgw:gempa:20211018231544"
+            ]
+        );
+        assert_eq!(texts(&xml, b"updated")[0], "2021-10-18T23:15:44+00:00");
+        assert_eq!(texts(&xml, b"georss:point"), vec!["-8.88 121.95"]);
+
+        let content = &texts(&xml, b"content")[0];
+        assert!(content.contains("<p>Depth: 10 km</p>"));
+        assert!(content.contains("<p>Coordinates: -8.88, 121.95</p>"));
+        assert!(content.contains("<p>Tsunami potential: Tidak berpotensi tsunami</p>"));
+        assert!(content
+            .contains("<img src=\"https://data.bmkg.go.id/DataMKG/TEWS/20211019061544.mmi.jpg\""));
+    }
+
+    #[test]
+    fn stable_entry_id_test() {
+        let terkini = parse_earthquakes(include_str!("../../fixtures/gempaterkini.xml")).unwrap();
+        let first = feed().to_xml(&terkini, Utc::now());
+        let next = feed().to_xml(&terkini[1..], Utc::now());
+        let ids = texts(&first, b"id");
+
+        assert_eq!(ids.len(), 1 + 8);
+        assert_eq!(&texts(&next, b"id")[1..], &ids[2..]);
+    }
+
+    #[test]
+    fn entry_without_time_test() {
+        let mut data = parse_earthquakes(include_str!("../../fixtures/gempaterkini.xml")).unwrap();
+        data[0].datetime = None;
+        data[0].tanggal = None;
+        let xml = feed().to_xml(&data, Utc::now());
+
+        assert_eq!(xml.matches("<entry>").count(), 7);
+        assert_eq!(texts(&xml, b"id").len(), 1 + 7);
+        assert_eq!(texts(&xml, b"updated").len(), 1 + 7);
+    }
+
+    #[test]
+    fn empty_feed_test() {
+        let now = Utc::now();
+        let xml = feed().to_xml(&[], now);
+
+        assert_eq!(texts(&xml, b"updated"), vec![now.to_rfc3339()]);
+        assert!(!xml.contains("<entry>"));
+    }
+}
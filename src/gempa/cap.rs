@@ -0,0 +1,477 @@
+//! Conversion of earthquakes into CAP 1.2 alerts.
+//!
+//! The alert carries an Indonesian and an English `info` block with category
+//! `Geo`, severity from magnitude and tsunami potential, and a circle around
+//! the epicenter. Serialize it with [`Alert::to_xml`].
+
+use super::Gempa;
+use crate::warning::{
+    Alert, Area, Category, Certainty, Circle, Info, MsgType, Parameter, Scope, Severity, Status,
+    Urgency,
+};
+use chrono::{DateTime, Utc};
+
+const SENDER_NAME: &str = "Badan Meteorologi, Klimatologi, dan Geofisika";
+
+/// CAP severity: `Extreme` with tsunami potential or from M7, then `Severe`
+/// from M6, `Moderate` from M5 and `Minor` below.
+pub fn severity(g: &Gempa) -> Severity {
+    if g.tsunami_potential() == Some(true) {
+        return Severity::Extreme;
+    }
+    match g.mag() {
+        Some(m) if m >= 7.0 => Severity::Extreme,
+        Some(m) if m >= 6.0 => Severity::Severe,
+        Some(m) if m >= 5.0 => Severity::Moderate,
+        Some(_) => Severity::Minor,
+        None => Severity::Unknown,
+    }
+}
+
+/// Radius in km of the alert circle, roughly where the event is felt:
+/// 10^(0.4·M), e.g. 100 km for M5 and 630 km for M7.
+pub fn circle_radius_km(magnitude: f64) -> f64 {
+    (10f64.powf(0.4 * magnitude)).round()
+}
+
+struct Text {
+    language: &'static str,
+    event: &'static str,
+    headline: String,
+    description: String,
+    instruction: Option<&'static str>,
+}
+
+fn texts(g: &Gempa) -> [Text; 2] {
+    let mag = g.magnitude.as_deref().unwrap_or("?").trim();
+    let wilayah = g.wilayah.as_deref().unwrap_or_default().trim();
+    let depth = g.kedalaman.as_deref().unwrap_or("?");
+    let (lat, lon) = (g.latitude(), g.longitude());
+    let tsunami = g.tsunami_potential();
+
+    let mut id = format!("Gempa magnitudo {}, kedalaman {}", mag, depth);
+    let mut en = format!("Magnitude {} earthquake, depth {}", mag, depth);
+    if let (Some(lintang), Some(bujur)) = (g.lintang.as_ref(), g.bujur.as_ref()) {
+        id.push_str(&format!(", pusat gempa {} {}", lintang, bujur));
+    }
+    if let (Some(lat), Some(lon)) = (lat, lon) {
+        en.push_str(&format!(", epicenter {}, {}", lat, lon));
+    }
+    if !wilayah.is_empty() {
+        id.push_str(&format!(" ({})", wilayah));
+        en.push_str(&format!(" ({})", wilayah));
+    }
+    id.push('.');
+    en.push('.');
+    if let Some(ref potensi) = g.potensi {
+        id.push_str(&format!(" {}.", potensi.trim_end_matches('.')));
+    }
+    match tsunami {
+        Some(true) => en.push_str(" Potential tsunami."),
+        Some(false) => en.push_str(" No tsunami potential."),
+        None => (),
+    }
+    if let Some(ref dirasakan) = g.dirasakan {
+        id.push_str(&format!(" Dirasakan (MMI): {}.", dirasakan));
+        en.push_str(&format!(" Felt (MMI): {}.", dirasakan));
+    }
+
+    let tsunami = tsunami == Some(true);
+    [
+        Text {
+            language: "id-ID",
+            event: "Gempabumi",
+            headline: format!("Gempa M{} {}", mag, wilayah).trim().to_string(),
+            description: id,
+            instruction: if tsunami {
+                Some("Segera menjauhi pantai dan menuju tempat yang lebih tinggi.")
+            } else {
+                None
+            },
+        },
+        Text {
+            language: "en-US",
+            event: "Earthquake",
+            headline: format!("M{} earthquake {}", mag, wilayah)
+                .trim()
+                .to_string(),
+            description: en,
+            instruction: if tsunami {
+                Some("Move away from the coast to higher ground immediately.")
+            } else {
+                None
+            },
+        },
+    ]
+}
+
+impl Gempa {
+    /// CAP alert for this earthquake issued by `sender` at `sent`, `None`
+    /// when its origin time is unknown.
+    pub fn to_cap(&self, sender: &str, sent: DateTime<Utc>) -> Option<Alert> {
+        let key = self.event_key()?;
+        let onset = self.time()?.fixed_offset();
+        let severity = severity(self);
+        let urgency = if self.tsunami_potential() == Some(true) {
+            Urgency::Immediate
+        } else {
+            Urgency::Past
+        };
+
+        let mut area = Area::new();
+        area.area_desc = Some(
+            self.wilayah
+                .clone()
+                .unwrap_or_else(|| "Epicenter".to_string()),
+        );
+        if let (Some(lat), Some(lon)) = (self.latitude(), self.longitude()) {
+            area.circles.push(Circle {
+                latitude: lat,
+                longitude: lon,
+                radius_km: circle_radius_km(self.mag().unwrap_or(5.0)),
+            });
+        }
+
+        let mut parameters = Vec::new();
+        if let Some(mag) = self.mag() {
+            parameters.push(Parameter {
+                value_name: "magnitude".to_string(),
+                value: mag.to_string(),
+            });
+        }
+        if let Some(depth) = self.depth_km() {
+            parameters.push(Parameter {
+                value_name: "depth_km".to_string(),
+                value: depth.to_string(),
+            });
+        }
+
+        let infos = texts(self)
+            .iter()
+            .map(|t| {
+                let mut info = Info::new();
+                info.language = Some(t.language.to_string());
+                info.categories = vec![Category::Geo];
+                info.event = Some(t.event.to_string());
+                info.urgency = Some(urgency);
+                info.severity = Some(severity);
+                info.certainty = Some(Certainty::Observed);
+                info.onset = Some(onset);
+                info.sender_name = Some(SENDER_NAME.to_string());
+                info.headline = Some(t.headline.clone());
+                info.description = Some(t.description.clone());
+                info.instruction = t.instruction.map(|x| x.to_string());
+                info.web = self.shakemap.clone();
+                info.parameters = parameters.clone();
+                info.areas = vec![area.clone()];
+                info
+            })
+            .collect();
+
+        let mut alert = Alert::new();
+        alert.identifier = Some(format!("bmkgw-gempa-{}", key));
+        alert.sender = Some(sender.to_string());
+        alert.sent = Some(sent.fixed_offset());
+        alert.status = Some(Status::Actual);
+        alert.msg_type = Some(MsgType::Alert);
+        alert.scope = Some(Scope::Public);
+        alert.infos = infos;
+        Some(alert)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+    use crate::warning::{parse_alert, CAP_NAMESPACE};
+    use chrono::TimeZone;
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    /// Child elements allowed by the CAP 1.2 schema, in sequence order, and
+    /// whether each is required.
+    fn content_model(parent: &str) -> &'static [(&'static str, bool)] {
+        match parent {
+            "alert" => &[
+                ("identifier", true),
+                ("sender", true),
+                ("sent", true),
+                ("status", true),
+                ("msgType", true),
+                ("source", false),
+                ("scope", true),
+                ("restriction", false),
+                ("addresses", false),
+                ("code", false),
+                ("note", false),
+                ("references", false),
+                ("incidents", false),
+                ("info", false),
+            ],
+            "info" => &[
+                ("language", false),
+                ("category", true),
+                ("event", true),
+                ("responseType", false),
+                ("urgency", true),
+                ("severity", true),
+                ("certainty", true),
+                ("audience", false),
+                ("eventCode", false),
+                ("effective", false),
+                ("onset", false),
+                ("expires", false),
+                ("senderName", false),
+                ("headline", false),
+                ("description", false),
+                ("instruction", false),
+                ("web", false),
+                ("contact", false),
+                ("parameter", false),
+                ("resource", false),
+                ("area", false),
+            ],
+            "area" => &[
+                ("areaDesc", true),
+                ("polygon", false),
+                ("circle", false),
+                ("geocode", false),
+                ("altitude", false),
+                ("ceiling", false),
+            ],
+            "parameter" => &[("valueName", true), ("value", true)],
+            _ => &[],
+        }
+    }
+
+    fn enumeration(element: &str) -> Option<&'static [&'static str]> {
+        Some(match element {
+            "status" => &["Actual", "Exercise", "System", "Test", "Draft"],
+            "msgType" => &["Alert", "Update", "Cancel", "Ack", "Error"],
+            "scope" => &["Public", "Restricted", "Private"],
+            "category" => &[
+                "Geo",
+                "Met",
+                "Safety",
+                "Security",
+                "Rescue",
+                "Fire",
+                "Health",
+                "Env",
+                "Transport",
+                "Infra",
+                "CBRNE",
+                "Other",
+            ],
+            "urgency" => &["Immediate", "Expected", "Future", "Past", "Unknown"],
+            "severity" => &["Extreme", "Severe", "Moderate", "Minor", "Unknown"],
+            "certainty" => &["Observed", "Likely", "Possible", "Unlikely", "Unknown"],
+            _ => return None,
+        })
+    }
+
+    fn is_cap_time(s: &str) -> bool {
+        // 2002-05-24T16:49:00-07:00; UTC must be written -00:00 (§3.3.2)
+        let b = s.as_bytes();
+        b.len() == 25
+            && chrono::DateTime::parse_from_rfc3339(s).is_ok()
+            && (b[19] == b'+' || b[19] == b'-')
+            && !s.ends_with("+00:00")
+    }
+
+    /// Checks `xml` against the CAP 1.2 constraints: namespace, element
+    /// order and cardinality, enumerations and date time format, including
+    /// the `-00:00` rule for UTC that the XSD pattern does not express.
+    fn validate(xml: &str) -> Result<(), String> {
+        let mut reader = Reader::from_str(xml);
+        reader.trim_text(true);
+        let mut buf = Vec::new();
+        // (element, children seen so far)
+        let mut stack: Vec<(String, Vec<String>)> = Vec::new();
+        let mut text = String::new();
+
+        loop {
+            match reader.read_event(&mut buf).map_err(|e| e.to_string())? {
+                Event::Start(ref e) => {
+                    let name = String::from_utf8_lossy(e.name()).into_owned();
+                    if stack.is_empty() {
+                        let ns = e
+                            .attributes()
+                            .filter_map(|a| a.ok())
+                            .find(|a| a.key == b"xmlns")
+                            .map(|a| String::from_utf8_lossy(&a.value).into_owned());
+                        if name != "alert" || ns.as_deref() != Some(CAP_NAMESPACE) {
+                            return Err("root must be a CAP 1.2 alert".to_string());
+                        }
+                    }
+                    if let Some((_, children)) = stack.last_mut() {
+                        children.push(name.clone());
+                    }
+                    stack.push((name, Vec::new()));
+                    text.clear();
+                }
+                Event::Text(ref e) => text = e.unescape_and_decode(&reader).unwrap(),
+                Event::End(_) => {
+                    let (name, children) = stack.pop().unwrap();
+                    let model = content_model(&name);
+                    let mut pos = 0;
+                    for child in children.iter() {
+                        let idx = model[pos..]
+                            .iter()
+                            .position(|(n, _)| n == child)
+                            .ok_or_else(|| format!("<{}> out of order in <{}>", child, name))?;
+                        pos += idx;
+                    }
+                    for (required, _) in model.iter().filter(|(_, r)| *r) {
+                        if !children.iter().any(|c| c == required) {
+                            return Err(format!("<{}> missing in <{}>", required, name));
+                        }
+                    }
+                    if let Some(allowed) = enumeration(&name) {
+                        if !allowed.contains(&text.as_str()) {
+                            return Err(format!("invalid <{}> {}", name, text));
+                        }
+                    }
+                    if ["sent", "effective", "onset", "expires"].contains(&name.as_str())
+                        && !is_cap_time(&text)
+                    {
+                        return Err(format!("invalid <{}> {}", name, text));
+                    }
+                    if name == "identifier" && text.contains(|c: char| " ,<&".contains(c)) {
+                        return Err(format!("invalid identifier {}", text));
+                    }
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+            buf.clear();
+        }
+
+        Ok(())
+    }
+
+    fn sent() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 10, 18, 23, 20, 0).unwrap()
+    }
+
+    #[test]
+    fn to_cap_test() {
+        let data = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+        let alert = data[0].to_cap("bmkgw@example.com", sent()).unwrap();
+
+        assert_eq!(
+            alert.identifier.as_deref(),
+            Some("bmkgw-gempa-20211018231544")
+        );
+        assert_eq!(alert.infos.len(), 2);
+
+        let id = alert.info("id").unwrap();
+        let en = alert.info("en").unwrap();
+        assert_eq!(id.categories, vec![Category::Geo]);
+        assert_eq!(id.severity, Some(Severity::Moderate));
+        assert_eq!(id.urgency, Some(Urgency::Past));
+        assert_eq!(id.event.as_deref(), Some("Gempabumi"));
+        assert_eq!(en.event.as_deref(), Some("Earthquake"));
+        assert_eq!(
+            en.headline.as_deref(),
+            Some("M5.2 earthquake 45 km BaratDaya KAB-SIKKA")
+        );
+        assert!(en
+            .description
+            .as_deref()
+            .unwrap()
+            .contains("No tsunami potential"));
+        assert!(id
+            .description
+            .as_deref()
+            .unwrap()
+            .contains("8.88 LS 121.95 BT"));
+        assert_eq!(
+            id.areas[0].circles,
+            vec![Circle {
+                latitude: -8.88,
+                longitude: 121.95,
+                radius_km: 120.0
+            }]
+        );
+    }
+
+    #[test]
+    fn to_cap_schema_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        for g in data.iter() {
+            let xml = g.to_cap("bmkgw@example.com", sent()).unwrap().to_xml();
+            validate(&xml).unwrap();
+            assert!(xml.contains("<sent>2021-10-18T23:20:00-00:00</sent>"));
+        }
+
+        let alert = data[1].to_cap("bmkgw@example.com", sent()).unwrap();
+        assert_eq!(parse_alert(alert.to_xml()).unwrap(), alert);
+    }
+
+    /// Validates with xmllint against the OASIS schema in
+    /// `fixtures/CAP-v1.2.xsd`; fails when xmllint is not installed.
+    #[test]
+    fn to_cap_xsd_test() {
+        use std::process::Command;
+
+        if let Err(e) = Command::new("xmllint").arg("--version").output() {
+            panic!(
+                "xmllint (libxml2) is needed for the CAP schema check: {}",
+                e
+            );
+        }
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        for (i, g) in data.iter().enumerate() {
+            let path =
+                std::env::temp_dir().join(format!("bmkgw-cap-{}-{}.xml", std::process::id(), i));
+            std::fs::write(
+                &path,
+                g.to_cap("bmkgw@example.com", sent()).unwrap().to_xml(),
+            )
+            .unwrap();
+            let output = Command::new("xmllint")
+                .args(["--noout", "--schema", "fixtures/CAP-v1.2.xsd"])
+                .arg(&path)
+                .output()
+                .unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+
+    #[test]
+    fn validate_rejects_test() {
+        let data = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+        let xml = data[0].to_cap("bmkgw", sent()).unwrap().to_xml();
+
+        let swapped = xml.replace(
+            "<status>Actual</status>\n<msgType>Alert</msgType>",
+            "<msgType>Alert</msgType>\n<status>Actual</status>",
+        );
+        assert!(validate(&swapped).is_err());
+        assert!(validate(&xml.replace("Moderate", "Medium")).is_err());
+        assert!(validate(&xml.replace("-00:00</sent>", "Z</sent>")).is_err());
+        assert!(validate(&xml.replace("-00:00</sent>", "+00:00</sent>")).is_err());
+        assert!(
+            validate(&xml.replace("<areaDesc>45 km BaratDaya KAB-SIKKA</areaDesc>", "")).is_err()
+        );
+    }
+
+    #[test]
+    fn severity_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let severities: Vec<Severity> = data.iter().map(severity).collect();
+
+        // index 2 is the M6.4 Mentawai event with tsunami potential
+        assert_eq!(severities[2], Severity::Extreme);
+        assert_eq!(severities[3], Severity::Moderate);
+        assert_eq!(severity(&Gempa::new()), Severity::Unknown);
+    }
+}
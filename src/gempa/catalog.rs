@@ -0,0 +1,422 @@
+//! Long-term SQLite earthquake catalog, enabled by the `sqlite` feature.
+//!
+//! The feeds only carry the latest events; upserting every poll into a
+//! [`Catalog`] keeps them keyed by [`Gempa::event_key`]. Fields missing from
+//! a later observation keep their stored value, so e.g. the `Dirasakan` of
+//! gempadirasakan and the `Shakemap` of autogempa end up on the same row.
+
+use super::Gempa;
+use crate::{sql_timestamp, Error};
+use chrono::{DateTime, Utc};
+use rusqlite::types::ToSql;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS gempa (
+    event_key TEXT PRIMARY KEY,
+    time INTEGER NOT NULL,
+    magnitude REAL,
+    depth_km REAL,
+    latitude REAL,
+    longitude REAL,
+    tanggal TEXT,
+    jam TEXT,
+    datetime TEXT,
+    coordinates TEXT,
+    lintang TEXT,
+    bujur TEXT,
+    magnitude_text TEXT,
+    kedalaman TEXT,
+    wilayah TEXT,
+    potensi TEXT,
+    dirasakan TEXT,
+    shakemap TEXT,
+    first_seen INTEGER NOT NULL,
+    last_seen INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS gempa_time ON gempa (time);
+CREATE INDEX IF NOT EXISTS gempa_magnitude ON gempa (magnitude);
+";
+
+const COLUMNS: &str = "event_key, tanggal, jam, datetime, coordinates, lintang, bujur, \
+                       magnitude_text, kedalaman, wilayah, potensi, dirasakan, shakemap, \
+                       first_seen, last_seen";
+
+/// Latitude/longitude rectangle in decimal degrees, bounds inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub min_latitude: f64,
+    pub max_latitude: f64,
+    pub min_longitude: f64,
+    pub max_longitude: f64,
+}
+
+/// Catalog filter; every unset field matches everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Query {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub min_magnitude: Option<f64>,
+    pub max_magnitude: Option<f64>,
+    pub bbox: Option<BoundingBox>,
+    /// Case-insensitive substring of `Wilayah`, e.g. "sikka".
+    pub region: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// A stored event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub event_key: String,
+    pub gempa: Gempa,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Outcome of [`Catalog::upsert_all`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Upserted {
+    /// Events not in the catalog before.
+    pub added: usize,
+    /// Events left out because they have no origin time to key them by.
+    pub skipped: usize,
+}
+
+pub struct Catalog {
+    conn: Connection,
+}
+
+fn entry(row: &Row) -> rusqlite::Result<Entry> {
+    Ok(Entry {
+        event_key: row.get(0)?,
+        gempa: Gempa {
+            tanggal: row.get(1)?,
+            jam: row.get(2)?,
+            datetime: row.get(3)?,
+            coordinates: row.get(4)?,
+            lintang: row.get(5)?,
+            bujur: row.get(6)?,
+            magnitude: row.get(7)?,
+            kedalaman: row.get(8)?,
+            wilayah: row.get(9)?,
+            potensi: row.get(10)?,
+            dirasakan: row.get(11)?,
+            shakemap: row.get(12)?,
+        },
+        first_seen: sql_timestamp(row, 13)?,
+        last_seen: sql_timestamp(row, 14)?,
+    })
+}
+
+fn upsert(conn: &Connection, gempa: &Gempa, seen: DateTime<Utc>) -> Result<bool, Error> {
+    let key = gempa
+        .event_key()
+        .ok_or_else(|| Error::Others("earthquake without origin time".to_string()))?;
+    let time = gempa.time().map(|t| t.timestamp());
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM gempa WHERE event_key = ?1",
+            params![key],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+
+    conn.execute(
+        "INSERT INTO gempa (event_key, time, magnitude, depth_km, latitude, longitude,
+             tanggal, jam, datetime, coordinates, lintang, bujur, magnitude_text, kedalaman,
+             wilayah, potensi, dirasakan, shakemap, first_seen, last_seen)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+             ?17, ?18, ?19, ?19)
+         ON CONFLICT (event_key) DO UPDATE SET
+             magnitude = COALESCE(excluded.magnitude, magnitude),
+             depth_km = COALESCE(excluded.depth_km, depth_km),
+             latitude = COALESCE(excluded.latitude, latitude),
+             longitude = COALESCE(excluded.longitude, longitude),
+             tanggal = COALESCE(excluded.tanggal, tanggal),
+             jam = COALESCE(excluded.jam, jam),
+             datetime = COALESCE(excluded.datetime, datetime),
+             coordinates = COALESCE(excluded.coordinates, coordinates),
+             lintang = COALESCE(excluded.lintang, lintang),
+             bujur = COALESCE(excluded.bujur, bujur),
+             magnitude_text = COALESCE(excluded.magnitude_text, magnitude_text),
+             kedalaman = COALESCE(excluded.kedalaman, kedalaman),
+             wilayah = COALESCE(excluded.wilayah, wilayah),
+             potensi = COALESCE(excluded.potensi, potensi),
+             dirasakan = COALESCE(excluded.dirasakan, dirasakan),
+             shakemap = COALESCE(excluded.shakemap, shakemap),
+             first_seen = MIN(first_seen, excluded.first_seen),
+             last_seen = MAX(last_seen, excluded.last_seen)",
+        params![
+            key,
+            time,
+            gempa.mag(),
+            gempa.depth_km(),
+            gempa.latitude(),
+            gempa.longitude(),
+            gempa.tanggal,
+            gempa.jam,
+            gempa.datetime,
+            gempa.coordinates,
+            gempa.lintang,
+            gempa.bujur,
+            gempa.magnitude,
+            gempa.kedalaman,
+            gempa.wilayah,
+            gempa.potensi,
+            gempa.dirasakan,
+            gempa.shakemap,
+            seen.timestamp(),
+        ],
+    )?;
+
+    Ok(!exists)
+}
+
+impl Catalog {
+    /// Opens or creates the catalog database at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::init(Connection::open(path)?)
+    }
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::init(Connection::open_in_memory()?)
+    }
+    fn init(conn: Connection) -> Result<Self, Error> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Inserts or merges `gempa`, returning whether the event is new.
+    pub fn upsert(&self, gempa: &Gempa, seen: DateTime<Utc>) -> Result<bool, Error> {
+        upsert(&self.conn, gempa, seen)
+    }
+
+    /// Upserts a whole feed in one transaction. Events without an origin
+    /// time are skipped and counted rather than failing the feed.
+    pub fn upsert_all(&mut self, data: &[Gempa], seen: DateTime<Utc>) -> Result<Upserted, Error> {
+        let tx = self.conn.transaction()?;
+        let mut res = Upserted::default();
+        for gempa in data {
+            if gempa.event_key().is_none() {
+                res.skipped += 1;
+            } else if upsert(&tx, gempa, seen)? {
+                res.added += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(res)
+    }
+
+    pub fn get(&self, event_key: &str) -> Result<Option<Entry>, Error> {
+        let sql = format!("SELECT {} FROM gempa WHERE event_key = ?1", COLUMNS);
+        Ok(self
+            .conn
+            .query_row(&sql, params![event_key], entry)
+            .optional()?)
+    }
+
+    pub fn len(&self) -> Result<usize, Error> {
+        let n: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM gempa", [], |row| row.get(0))?;
+        Ok(n as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        self.len().map(|n| n == 0)
+    }
+
+    /// Events matching `query`, most recent first.
+    pub fn query(&self, query: &Query) -> Result<Vec<Entry>, Error> {
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+
+        if let Some(start) = query.start {
+            conditions.push("time >= ?");
+            values.push(Box::new(start.timestamp()));
+        }
+        if let Some(end) = query.end {
+            conditions.push("time <= ?");
+            values.push(Box::new(end.timestamp()));
+        }
+        if let Some(min) = query.min_magnitude {
+            conditions.push("magnitude >= ?");
+            values.push(Box::new(min));
+        }
+        if let Some(max) = query.max_magnitude {
+            conditions.push("magnitude <= ?");
+            values.push(Box::new(max));
+        }
+        if let Some(bbox) = query.bbox {
+            conditions.push("latitude BETWEEN ? AND ?");
+            values.push(Box::new(bbox.min_latitude));
+            values.push(Box::new(bbox.max_latitude));
+            conditions.push("longitude BETWEEN ? AND ?");
+            values.push(Box::new(bbox.min_longitude));
+            values.push(Box::new(bbox.max_longitude));
+        }
+        if let Some(ref region) = query.region {
+            conditions.push("instr(lower(wilayah), lower(?)) > 0");
+            values.push(Box::new(region.clone()));
+        }
+
+        let mut sql = format!("SELECT {} FROM gempa", COLUMNS);
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY time DESC");
+        if let Some(limit) = query.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values.iter()), entry)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+    use chrono::TimeZone;
+
+    fn seen(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 10, day, 0, 0, 0).unwrap()
+    }
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::in_memory().unwrap();
+        let terkini = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let dirasakan = parse_earthquakes_file("fixtures/gempadirasakan.xml").unwrap();
+        let auto = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+
+        assert_eq!(catalog.upsert_all(&terkini, seen(19)).unwrap().added, 8);
+        assert_eq!(catalog.upsert_all(&dirasakan, seen(20)).unwrap().added, 3);
+        assert_eq!(catalog.upsert_all(&auto, seen(20)).unwrap().added, 0);
+        catalog
+    }
+
+    #[test]
+    fn upsert_merge_test() {
+        let catalog = catalog();
+        let sikka = catalog.get("20211018231544").unwrap().unwrap();
+
+        assert_eq!(catalog.len().unwrap(), 11);
+        assert!(sikka.gempa.dirasakan.is_some());
+        assert_eq!(
+            sikka.gempa.shakemap.as_deref(),
+            Some("https://data.bmkg.go.id/DataMKG/TEWS/20211019061544.mmi.jpg")
+        );
+        assert_eq!(sikka.first_seen, seen(19));
+        assert_eq!(sikka.last_seen, seen(20));
+        assert!(catalog.get("19700101000000").unwrap().is_none());
+    }
+
+    #[test]
+    fn query_test() {
+        let catalog = catalog();
+        let keys = |q: &Query| -> Vec<String> {
+            catalog
+                .query(q)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.event_key)
+                .collect()
+        };
+
+        let recent = Query {
+            start: Some(seen(17)),
+            ..Query::default()
+        };
+        assert_eq!(
+            keys(&recent),
+            vec![
+                "20211019010210",
+                "20211018231544",
+                "20211018092011",
+                "20211017034000"
+            ]
+        );
+
+        let strong = Query {
+            min_magnitude: Some(5.8),
+            ..Query::default()
+        };
+        assert_eq!(keys(&strong), vec!["20211017034000", "20211010021500"]);
+
+        let sikka = Query {
+            region: Some("sikka".to_string()),
+            limit: Some(1),
+            ..Query::default()
+        };
+        assert_eq!(keys(&sikka), vec!["20211019010210"]);
+
+        let java = Query {
+            bbox: Some(BoundingBox {
+                min_latitude: -9.0,
+                max_latitude: -6.0,
+                min_longitude: 105.0,
+                max_longitude: 115.0,
+            }),
+            max_magnitude: Some(5.0),
+            ..Query::default()
+        };
+        assert_eq!(keys(&java), vec!["20211015080012", "20211013174530"]);
+    }
+
+    #[test]
+    fn upsert_all_skips_keyless_test() {
+        let mut catalog = Catalog::in_memory().unwrap();
+        let mut data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        data[1].datetime = None;
+        data[1].tanggal = None;
+        data[3].datetime = None;
+        data[3].tanggal = None;
+        assert!(data[1].event_key().is_none());
+
+        let res = catalog.upsert_all(&data, seen(19)).unwrap();
+        assert_eq!(
+            res,
+            Upserted {
+                added: 6,
+                skipped: 2
+            }
+        );
+        assert_eq!(catalog.len().unwrap(), 6);
+    }
+
+    #[test]
+    fn corrupt_timestamp_test() {
+        let catalog = catalog();
+        catalog
+            .conn
+            .execute(
+                "UPDATE gempa SET last_seen = ?1 WHERE event_key = '20211018231544'",
+                params![i64::MAX],
+            )
+            .unwrap();
+
+        assert!(catalog.get("20211018231544").is_err());
+        assert!(catalog.get("20211019010210").unwrap().is_some());
+    }
+
+    #[test]
+    fn open_file_test() {
+        let path = std::env::temp_dir().join(format!("bmkgw-catalog-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let catalog = Catalog::open(&path).unwrap();
+            let auto = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+            assert!(catalog.upsert(&auto[0], seen(19)).unwrap());
+            assert!(!catalog.upsert(&auto[0], seen(20)).unwrap());
+        }
+
+        let catalog = Catalog::open(&path).unwrap();
+        assert_eq!(catalog.len().unwrap(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+//! Mainshock–aftershock declustering with Gardner–Knopoff (1974) windows.
+//!
+//! Events are taken in decreasing magnitude; each one not yet assigned
+//! starts a cluster and claims every unassigned event within its distance
+//! window and, after it, within its time window. The windows follow the
+//! usual fit of the original table:
+//!
+//! - distance: `10^(0.1238·M + 0.983)` km
+//! - time: `10^(0.032·M + 2.7389)` days from M6.5, `10^(0.5409·M − 0.547)` below
+
+use super::Gempa;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// Fraction of the time window also searched before the mainshock for
+    /// foreshocks, 0 to only collect aftershocks.
+    pub foreshock_ratio: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            foreshock_ratio: 0.0,
+        }
+    }
+}
+
+/// Distance window in km for magnitude `m`.
+pub fn distance_window_km(m: f64) -> f64 {
+    10f64.powf(0.1238 * m + 0.983)
+}
+
+/// Time window in days for magnitude `m`.
+pub fn time_window_days(m: f64) -> f64 {
+    if m >= 6.5 {
+        10f64.powf(0.032 * m + 2.7389)
+    } else {
+        10f64.powf(0.5409 * m - 0.547)
+    }
+}
+
+/// A mainshock and the events in its windows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cluster {
+    pub mainshock: Gempa,
+    /// Foreshocks and aftershocks, oldest first.
+    pub members: Vec<Gempa>,
+}
+
+impl Cluster {
+    /// Number of events, mainshock included.
+    pub fn event_count(&self) -> usize {
+        1 + self.members.len()
+    }
+    /// Origin times of the first and last event.
+    pub fn span(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let times = std::iter::once(&self.mainshock)
+            .chain(self.members.iter())
+            .filter_map(|g| g.time());
+        let first = times.clone().min()?;
+        let last = times.max()?;
+        Some((first, last))
+    }
+    pub fn aftershocks(&self) -> impl Iterator<Item = &Gempa> {
+        let t = self.mainshock.time();
+        self.members.iter().filter(move |g| g.time() >= t)
+    }
+    pub fn foreshocks(&self) -> impl Iterator<Item = &Gempa> {
+        let t = self.mainshock.time();
+        self.members.iter().filter(move |g| g.time() < t)
+    }
+    pub fn to_json(self) -> JsonValue {
+        json!(self)
+    }
+}
+
+struct Event<'a> {
+    gempa: &'a Gempa,
+    time: DateTime<Utc>,
+    mag: f64,
+    lat: f64,
+    lon: f64,
+}
+
+/// Groups `data` into clusters, most recent mainshock first. Events whose
+/// time, coordinates or magnitude are unknown become clusters of their own.
+pub fn decluster(data: &[Gempa], config: &Config) -> Vec<Cluster> {
+    let mut clusters: Vec<Cluster> = Vec::new();
+    let mut events: Vec<Event> = Vec::new();
+    for g in data {
+        match (g.time(), g.mag(), g.latitude(), g.longitude()) {
+            (Some(time), Some(mag), Some(lat), Some(lon)) => events.push(Event {
+                gempa: g,
+                time,
+                mag,
+                lat,
+                lon,
+            }),
+            _ => clusters.push(Cluster {
+                mainshock: g.clone(),
+                members: Vec::new(),
+            }),
+        }
+    }
+
+    // largest first, the earlier one on ties
+    events.sort_by(|a, b| b.mag.total_cmp(&a.mag).then(a.time.cmp(&b.time)));
+    let mut assigned = vec![false; events.len()];
+
+    for i in 0..events.len() {
+        if assigned[i] {
+            continue;
+        }
+        assigned[i] = true;
+        let main = &events[i];
+        let distance = distance_window_km(main.mag);
+        let days = time_window_days(main.mag);
+
+        let mut members: Vec<&Event> = Vec::new();
+        for (j, e) in events.iter().enumerate() {
+            if assigned[j] {
+                continue;
+            }
+            let dt = (e.time - main.time).num_seconds() as f64 / 86400.0;
+            let in_time = if dt >= 0.0 {
+                dt <= days
+            } else {
+                -dt <= days * config.foreshock_ratio
+            };
+            if in_time && crate::geo::distance_km(main.lat, main.lon, e.lat, e.lon) <= distance {
+                assigned[j] = true;
+                members.push(e);
+            }
+        }
+        members.sort_by_key(|e| e.time);
+
+        clusters.push(Cluster {
+            mainshock: main.gempa.clone(),
+            members: members.iter().map(|e| e.gempa.clone()).collect(),
+        });
+    }
+
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.mainshock.time()));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn quake(hours: i64, lat: f64, lon: f64, mag: f64) -> Gempa {
+        let t = Utc.with_ymd_and_hms(2021, 10, 17, 3, 40, 0).unwrap() + Duration::hours(hours);
+        let mut g = Gempa::new();
+        g.datetime = Some(t.to_rfc3339());
+        g.coordinates = Some(format!("{},{}", lat, lon));
+        g.magnitude = Some(mag.to_string());
+        g
+    }
+
+    fn keys(gs: &[Gempa]) -> Vec<String> {
+        gs.iter().map(|g| g.event_key().unwrap()).collect()
+    }
+
+    #[test]
+    fn windows_test() {
+        assert!((distance_window_km(5.0) - 40.0).abs() < 1.0);
+        assert!((time_window_days(5.0) - 143.7).abs() < 0.1);
+        assert!((time_window_days(7.0) - 918.1).abs() < 0.1);
+        assert!(distance_window_km(7.0) > distance_window_km(6.0));
+    }
+
+    #[test]
+    fn decluster_test() {
+        let data = vec![
+            quake(-24, -1.52, 99.1, 4.8), // foreshock
+            quake(0, -1.5, 99.0, 6.4),    // mainshock, Mentawai
+            quake(2, -1.6, 99.2, 5.1),
+            quake(30, -1.3, 98.8, 4.5),
+            quake(24 * 900, -1.5, 99.0, 4.6), // after the M6.4 time window (822 days)
+            quake(5, -8.9, 121.9, 5.2),       // far away, Sikka
+            quake(6, -8.95, 121.95, 4.0),     // Sikka aftershock
+        ];
+        let clusters = decluster(&data, &Config::default());
+
+        assert_eq!(clusters.len(), 4);
+        let mentawai = clusters.iter().find(|c| c.event_count() == 3).unwrap();
+        assert_eq!(mentawai.mainshock.mag(), Some(6.4));
+        assert_eq!(keys(&mentawai.members), keys(&data[2..4]));
+        assert_eq!(mentawai.aftershocks().count(), 2);
+
+        let sikka = clusters
+            .iter()
+            .find(|c| c.mainshock.mag() == Some(5.2))
+            .unwrap();
+        assert_eq!(keys(&sikka.members), keys(&data[6..7]));
+
+        // the foreshock stays on its own without a foreshock window
+        assert!(clusters
+            .iter()
+            .any(|c| c.mainshock.mag() == Some(4.8) && c.members.is_empty()));
+        // most recent mainshock first
+        assert_eq!(clusters[0].mainshock.mag(), Some(4.6));
+    }
+
+    #[test]
+    fn decluster_foreshock_test() {
+        let data = vec![
+            quake(-24, -1.52, 99.1, 4.8),
+            quake(0, -1.5, 99.0, 6.4),
+            quake(2, -1.6, 99.2, 5.1),
+        ];
+        let clusters = decluster(
+            &data,
+            &Config {
+                foreshock_ratio: 0.1,
+            },
+        );
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].foreshocks().count(), 1);
+        let (first, last) = clusters[0].span().unwrap();
+        assert_eq!(last - first, Duration::hours(26));
+    }
+
+    #[test]
+    fn decluster_incomplete_test() {
+        let mut unknown = quake(0, -1.5, 99.0, 6.4);
+        unknown.magnitude = None;
+        let clusters = decluster(&[unknown, quake(1, -1.5, 99.0, 5.0)], &Config::default());
+
+        assert_eq!(clusters.len(), 2);
+        assert!(clusters.iter().all(|c| c.members.is_empty()));
+    }
+}
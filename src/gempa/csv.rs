@@ -0,0 +1,76 @@
+//! CSV export of earthquake lists with parsed numeric columns.
+
+use super::Gempa;
+use crate::Error;
+use std::io::Write;
+
+const COLUMNS: [&str; 12] = [
+    "event_key",
+    "time",
+    "magnitude",
+    "depth_km",
+    "latitude",
+    "longitude",
+    "region",
+    "tsunami_potential",
+    "felt_intensity",
+    "potensi",
+    "dirasakan",
+    "shakemap",
+];
+
+fn cell<T: ToString>(x: Option<T>) -> String {
+    x.map(|x| x.to_string()).unwrap_or_default()
+}
+
+/// Writes one row per earthquake to `w` as they are iterated.
+pub fn write_earthquakes<'a, W, I>(data: I, w: W) -> Result<(), Error>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Gempa>,
+{
+    let mut writer = ::csv::Writer::from_writer(w);
+    writer.write_record(COLUMNS)?;
+
+    for g in data {
+        writer.write_record([
+            cell(g.event_key()),
+            cell(g.time().map(|t| t.to_rfc3339())),
+            cell(g.mag()),
+            cell(g.depth_km()),
+            cell(g.latitude()),
+            cell(g.longitude()),
+            cell(g.wilayah.as_ref()),
+            cell(g.tsunami_potential()),
+            cell(g.felt_intensity()),
+            cell(g.potensi.as_ref()),
+            cell(g.dirasakan.as_ref()),
+            cell(g.shakemap.as_ref()),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes;
+
+    #[test]
+    fn write_earthquakes_test() {
+        let data = parse_earthquakes(include_str!("../../fixtures/gempaterkini.xml")).unwrap();
+        let mut out = Vec::new();
+        write_earthquakes(&data, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 1 + data.len());
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert!(lines[2].starts_with(
+            "20211018231544,2021-10-18T23:15:44+00:00,5.2,10,-8.88,121.95,45 km BaratDaya KAB-SIKKA,false,,"
+        ));
+        assert!(lines[3].contains(",true,,"));
+    }
+}
@@ -0,0 +1,188 @@
+//! Radiated energy and seismic moment estimates from magnitude.
+//!
+//! BMKG magnitudes are treated as moment magnitudes. Seismic moment follows
+//! Hanks & Kanamori (1979), `log10 M0 = 1.5·Mw + 9.1` (N·m), and radiated
+//! energy the Gutenberg–Richter relation `log10 E = 1.5·M + 4.8` (J).
+
+use super::Gempa;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+/// Energy of one tonne of TNT in joules.
+pub const TNT_TONNE_JOULES: f64 = 4.184e9;
+/// Yield of the Hiroshima bomb, ~15 kt of TNT, a common briefing yardstick.
+pub const HIROSHIMA_TNT_TONNES: f64 = 15_000.0;
+
+/// Seismic moment in N·m.
+pub fn seismic_moment(magnitude: f64) -> f64 {
+    10f64.powf(1.5 * magnitude + 9.1)
+}
+
+/// Moment magnitude of a seismic moment in N·m.
+pub fn moment_magnitude(moment: f64) -> f64 {
+    (moment.log10() - 9.1) / 1.5
+}
+
+/// Radiated energy in joules.
+pub fn radiated_energy(magnitude: f64) -> f64 {
+    10f64.powf(1.5 * magnitude + 4.8)
+}
+
+impl Gempa {
+    pub fn seismic_moment(&self) -> Option<f64> {
+        self.mag().map(seismic_moment)
+    }
+    pub fn energy_joules(&self) -> Option<f64> {
+        self.mag().map(radiated_energy)
+    }
+    /// Radiated energy in tonnes of TNT.
+    pub fn tnt_tonnes(&self) -> Option<f64> {
+        self.energy_joules().map(|e| e / TNT_TONNE_JOULES)
+    }
+}
+
+/// Energy released by a set of earthquakes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnergyRelease {
+    /// Events with a magnitude.
+    pub count: usize,
+    pub energy_joules: f64,
+    pub tnt_tonnes: f64,
+    pub seismic_moment: f64,
+    /// Magnitude of a single event releasing the summed moment.
+    pub equivalent_magnitude: Option<f64>,
+    /// Event key of the largest event and its share of the energy, 0–1.
+    pub largest: Option<(String, f64)>,
+}
+
+impl EnergyRelease {
+    pub fn new<'a, I: IntoIterator<Item = &'a Gempa>>(data: I) -> Self {
+        let mut release = Self {
+            count: 0,
+            energy_joules: 0.0,
+            tnt_tonnes: 0.0,
+            seismic_moment: 0.0,
+            equivalent_magnitude: None,
+            largest: None,
+        };
+        let mut largest: Option<(&Gempa, f64)> = None;
+
+        for g in data {
+            let m = match g.mag() {
+                Some(m) => m,
+                None => continue,
+            };
+            let energy = radiated_energy(m);
+            release.count += 1;
+            release.energy_joules += energy;
+            release.seismic_moment += seismic_moment(m);
+            if largest.is_none_or(|(_, e)| energy > e) {
+                largest = Some((g, energy));
+            }
+        }
+
+        release.tnt_tonnes = release.energy_joules / TNT_TONNE_JOULES;
+        if release.count > 0 {
+            release.equivalent_magnitude = Some(moment_magnitude(release.seismic_moment));
+        }
+        release.largest =
+            largest.map(|(g, e)| (g.event_key().unwrap_or_default(), e / release.energy_joules));
+        release
+    }
+
+    /// Events with origin time in `[start, end)`.
+    pub fn in_window(data: &[Gempa], start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self::new(
+            data.iter()
+                .filter(|g| g.time().is_some_and(|t| t >= start && t < end)),
+        )
+    }
+
+    /// Events whose epicenter lies within `radius_km` of a point.
+    pub fn within(data: &[Gempa], latitude: f64, longitude: f64, radius_km: f64) -> Self {
+        Self::new(data.iter().filter(|g| {
+            g.distance_km(latitude, longitude)
+                .is_some_and(|d| d <= radius_km)
+        }))
+    }
+
+    /// Events whose `Wilayah` contains `region`, case-insensitively.
+    pub fn in_region(data: &[Gempa], region: &str) -> Self {
+        let region = region.to_lowercase();
+        Self::new(data.iter().filter(|g| {
+            g.wilayah
+                .as_ref()
+                .is_some_and(|w| w.to_lowercase().contains(&region))
+        }))
+    }
+
+    /// Energy in multiples of the Hiroshima bomb.
+    pub fn hiroshima_equivalents(&self) -> f64 {
+        self.tnt_tonnes / HIROSHIMA_TNT_TONNES
+    }
+
+    pub fn to_json(self) -> JsonValue {
+        json!(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+    use chrono::TimeZone;
+
+    fn close(a: f64, b: f64) -> bool {
+        ((a - b) / b).abs() < 1e-9
+    }
+
+    #[test]
+    fn estimates_test() {
+        // Mw 6 ≈ 1.26e18 N·m and 6.3e13 J, ~15 kt of TNT
+        assert!(close(seismic_moment(6.0), 10f64.powf(18.1)));
+        assert!(close(radiated_energy(6.0), 10f64.powf(13.8)));
+        assert!((moment_magnitude(seismic_moment(5.2)) - 5.2).abs() < 1e-12);
+
+        let data = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+        let g = &data[0];
+        assert!(close(g.energy_joules().unwrap(), 10f64.powf(12.6)));
+        assert!((g.tnt_tonnes().unwrap() - 951.5).abs() < 0.1);
+        assert!(Gempa::new().seismic_moment().is_none());
+    }
+
+    #[test]
+    fn energy_release_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let all = EnergyRelease::new(&data);
+
+        assert_eq!(all.count, 8);
+        let (key, share) = all.largest.clone().unwrap();
+        assert_eq!(key, "20211017034000");
+        assert!(share > 0.5 && share < 1.0);
+        // the sum is dominated by the M6.4 event
+        let mw = all.equivalent_magnitude.unwrap();
+        assert!(mw > 6.4 && mw < 6.6);
+        assert!(close(all.tnt_tonnes, all.energy_joules / TNT_TONNE_JOULES));
+    }
+
+    #[test]
+    fn energy_release_filters_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+
+        let start = Utc.with_ymd_and_hms(2021, 10, 18, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2021, 10, 20, 0, 0, 0).unwrap();
+        assert_eq!(EnergyRelease::in_window(&data, start, end).count, 2);
+
+        let sikka = EnergyRelease::in_region(&data, "sikka");
+        assert_eq!(sikka.count, 2);
+        assert!(close(
+            sikka.energy_joules,
+            radiated_energy(5.0) + radiated_energy(5.2)
+        ));
+
+        // Maumere, Sikka
+        assert_eq!(EnergyRelease::within(&data, -8.62, 122.21, 100.0).count, 2);
+        assert_eq!(EnergyRelease::new(&[]).equivalent_magnitude, None);
+    }
+}
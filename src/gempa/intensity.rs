@@ -0,0 +1,181 @@
+//! Estimated shaking at a site.
+//!
+//! Intensity follows the hypocentral-distance intensity prediction equation
+//! of Allen, Wald & Worden (2012) for active crustal regions, derived from
+//! global data including Indonesian events:
+//!
+//! `MMI = c0 + c1·M + c2·ln√(R² + Rm²) + c4·ln(R/50)` (last term for R > 50 km),
+//! with `Rm = m1 + m2·exp(M − 5)` and R the hypocentral distance in km.
+//!
+//! Peak ground acceleration is derived from MMI with the relation of Wald et
+//! al. (1999): `MMI = 3.66·log10(PGA) − 1.66` from MMI V and
+//! `MMI = 2.20·log10(PGA) + 1.00` below, PGA in cm/s².
+
+use super::alert::Site;
+use super::{Gempa, MMI_SCALE};
+use serde::{Deserialize, Serialize};
+
+const C0: f64 = 2.085;
+const C1: f64 = 1.428;
+const C2: f64 = -1.402;
+const C4: f64 = 0.078;
+const M1: f64 = -0.209;
+const M2: f64 = 2.042;
+/// Standard deviation of the equation in MMI units.
+pub const MMI_SIGMA: f64 = 0.82;
+/// Depth assumed when the feed does not give one.
+pub const DEFAULT_DEPTH_KM: f64 = 10.0;
+const STANDARD_GRAVITY_CMS2: f64 = 980.665;
+
+/// Estimated MMI for magnitude `m` at hypocentral distance `r_km`, within 1–12.
+pub fn mmi(m: f64, r_km: f64) -> f64 {
+    let rm = M1 + M2 * (m - 5.0).exp();
+    let mut i = C0 + C1 * m + C2 * (r_km * r_km + rm * rm).sqrt().ln();
+    if r_km > 50.0 {
+        i += C4 * (r_km / 50.0).ln();
+    }
+    i.clamp(1.0, 12.0)
+}
+
+/// Peak ground acceleration in cm/s² for intensity `mmi`.
+pub fn pga_from_mmi(mmi: f64) -> f64 {
+    if mmi >= 5.0 {
+        10f64.powf((mmi + 1.66) / 3.66)
+    } else {
+        10f64.powf((mmi - 1.0) / 2.20)
+    }
+}
+
+/// Roman numeral of an intensity, rounded, e.g. "VI". NaN gives "I".
+pub fn roman(mmi: f64) -> &'static str {
+    let mmi = if mmi.is_nan() { 1.0 } else { mmi };
+    let idx = (mmi.round().clamp(1.0, 12.0) as usize) - 1;
+    MMI_SCALE[idx]
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shaking {
+    pub epicentral_km: f64,
+    pub hypocentral_km: f64,
+    pub mmi: f64,
+    /// `mmi` as a roman numeral, like the `Dirasakan` field.
+    pub mmi_class: String,
+    pub pga_cms2: f64,
+    /// Peak ground acceleration as a fraction of g.
+    pub pga_g: f64,
+}
+
+/// A site and its estimated shaking.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SiteShaking {
+    pub site: Site,
+    pub shaking: Shaking,
+}
+
+impl Gempa {
+    /// Estimated shaking at a point, `None` without magnitude or epicenter.
+    pub fn shaking_at(&self, latitude: f64, longitude: f64) -> Option<Shaking> {
+        let m = self.mag()?;
+        let epicentral_km = self.distance_km(latitude, longitude)?;
+        let depth = self.depth_km().unwrap_or(DEFAULT_DEPTH_KM);
+        let hypocentral_km = (epicentral_km * epicentral_km + depth * depth).sqrt();
+        let mmi = mmi(m, hypocentral_km);
+        let pga_cms2 = pga_from_mmi(mmi);
+
+        Some(Shaking {
+            epicentral_km,
+            hypocentral_km,
+            mmi,
+            mmi_class: roman(mmi).to_string(),
+            pga_cms2,
+            pga_g: pga_cms2 / STANDARD_GRAVITY_CMS2,
+        })
+    }
+
+    /// Sites with an estimated intensity of at least `min_mmi`, strongest first.
+    pub fn affected_sites(&self, sites: &[Site], min_mmi: f64) -> Vec<SiteShaking> {
+        let mut affected: Vec<SiteShaking> = sites
+            .iter()
+            .filter_map(|s| {
+                let shaking = self.shaking_at(s.latitude, s.longitude)?;
+                Some(SiteShaking {
+                    site: s.clone(),
+                    shaking,
+                })
+            })
+            .filter(|s| s.shaking.mmi >= min_mmi)
+            .collect();
+        affected.sort_by(|a, b| b.shaking.mmi.total_cmp(&a.shaking.mmi));
+        affected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+
+    #[test]
+    fn mmi_test() {
+        // M6 at 10 km: Rm = 5.342, MMI = 2.085 + 8.568 − 1.402·ln(11.34)
+        assert!((mmi(6.0, 10.0) - 7.25).abs() < 0.01);
+        // the far-field term kicks in past 50 km
+        let at_100 = C0 + C1 * 6.0 + C2 * (100f64.powi(2) + 5.342f64.powi(2)).sqrt().ln();
+        assert!((mmi(6.0, 100.0) - (at_100 + C4 * 2f64.ln())).abs() < 0.01);
+        assert!(mmi(5.0, 20.0) > mmi(5.0, 80.0));
+        assert_eq!(mmi(3.0, 1000.0), 1.0);
+    }
+
+    #[test]
+    fn pga_test() {
+        // MMI VI ≈ 124 cm/s², MMI IV ≈ 23 cm/s²
+        assert!((pga_from_mmi(6.0) - 10f64.powf(7.66 / 3.66)).abs() < 1e-9);
+        assert!((pga_from_mmi(4.0) - 10f64.powf(3.0 / 2.2)).abs() < 1e-9);
+        assert!(pga_from_mmi(5.0) > pga_from_mmi(4.99));
+        assert_eq!(roman(5.6), "VI");
+        assert_eq!(roman(0.2), "I");
+        assert_eq!(roman(f64::NAN), "I");
+        assert_eq!(roman(f64::INFINITY), "XII");
+    }
+
+    #[test]
+    fn shaking_at_test() {
+        let data = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+        let g = &data[0];
+
+        // Maumere, reported as MMI III in Dirasakan
+        let maumere = g.shaking_at(-8.62, 122.21).unwrap();
+        assert!(
+            (maumere.hypocentral_km - (maumere.epicentral_km.powi(2) + 100.0).sqrt()).abs() < 1e-9
+        );
+        assert!(maumere.mmi > 3.0 && maumere.mmi < 5.5, "{}", maumere.mmi);
+        assert!((maumere.pga_g - maumere.pga_cms2 / 980.665).abs() < 1e-12);
+
+        let kupang = g.shaking_at(-10.17, 123.6).unwrap();
+        assert!(kupang.mmi < maumere.mmi);
+
+        let mut bogus = g.clone();
+        bogus.magnitude = Some("NaN".to_string());
+        assert!(bogus.shaking_at(-8.62, 122.21).is_none());
+    }
+
+    #[test]
+    fn affected_sites_test() {
+        let data = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+        let site = |name: &str, latitude, longitude| Site {
+            name: name.to_string(),
+            latitude,
+            longitude,
+        };
+        let sites = [
+            site("Kupang", -10.17, 123.6),
+            site("Maumere", -8.62, 122.21),
+            site("Ende", -8.84, 121.66),
+            site("Jakarta", -6.2, 106.82),
+        ];
+        let affected = data[0].affected_sites(&sites, 3.0);
+        let names: Vec<&str> = affected.iter().map(|s| s.site.name.as_str()).collect();
+
+        assert_eq!(names, vec!["Ende", "Maumere"]);
+    }
+}
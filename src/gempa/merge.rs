@@ -0,0 +1,332 @@
+//! One record per earthquake across the `Autogempa`, `GempaTerkini` and
+//! `GempaDirasakan` feeds.
+//!
+//! Reports from different feeds are the same event when their origin times
+//! are within `time_tolerance_secs` and, when both have an epicenter, their
+//! epicenters within `distance_tolerance_km`. Each field of the merged record
+//! comes from the first feed in `precedence` that has it; the date and time
+//! fields, and the coordinate fields, are taken together from one feed.
+
+use super::{Gempa, Url};
+use crate::Error;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::collections::BTreeMap;
+
+/// Fields copied together, named as in the serialized `Gempa`.
+const FIELD_GROUPS: [&[&str]; 8] = [
+    &["tanggal", "jam", "datetime"],
+    &["coordinates", "lintang", "bujur"],
+    &["magnitude"],
+    &["kedalaman"],
+    &["wilayah"],
+    &["potensi"],
+    &["dirasakan"],
+    &["shakemap"],
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    pub time_tolerance_secs: i64,
+    pub distance_tolerance_km: f64,
+    /// Feeds in order of preference for each field; unlisted feeds come last.
+    pub precedence: Vec<Url>,
+}
+
+impl Default for Config {
+    /// Felt reports are published after review, so `GempaDirasakan` wins
+    /// over the automatic feeds.
+    fn default() -> Self {
+        Self {
+            time_tolerance_secs: 60,
+            distance_tolerance_km: 50.0,
+            precedence: vec![Url::GempaDirasakan, Url::Autogempa, Url::GempaTerkini],
+        }
+    }
+}
+
+impl Config {
+    fn rank(&self, url: Url) -> usize {
+        self.precedence
+            .iter()
+            .position(|x| *x == url)
+            .unwrap_or(usize::MAX)
+    }
+    fn same_event(&self, a: &Gempa, b: &Gempa) -> bool {
+        let within_time = match (a.time(), b.time()) {
+            (Some(x), Some(y)) => (x - y).num_seconds().abs() <= self.time_tolerance_secs,
+            _ => false,
+        };
+        let within_distance = match (a.latitude(), a.longitude()) {
+            (Some(lat), Some(lon)) => b
+                .distance_km(lat, lon)
+                .is_none_or(|d| d <= self.distance_tolerance_km),
+            _ => true,
+        };
+        within_time && within_distance
+    }
+}
+
+/// An earthquake combined from every feed that reported it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedEvent {
+    pub gempa: Gempa,
+    /// Feeds that reported the event, in order of precedence.
+    pub sources: Vec<Url>,
+    /// Feed each present field of `gempa` was taken from, by field name.
+    pub provenance: BTreeMap<String, Url>,
+}
+
+impl MergedEvent {
+    /// Feed the field was taken from, e.g. `source("shakemap")`.
+    pub fn source(&self, field: &str) -> Option<Url> {
+        self.provenance.get(field).copied()
+    }
+    pub fn to_json(self) -> JsonValue {
+        json!(self)
+    }
+}
+
+fn field<'a>(g: &'a mut Gempa, name: &str) -> &'a mut Option<String> {
+    match name {
+        "tanggal" => &mut g.tanggal,
+        "jam" => &mut g.jam,
+        "datetime" => &mut g.datetime,
+        "coordinates" => &mut g.coordinates,
+        "lintang" => &mut g.lintang,
+        "bujur" => &mut g.bujur,
+        "magnitude" => &mut g.magnitude,
+        "kedalaman" => &mut g.kedalaman,
+        "wilayah" => &mut g.wilayah,
+        "potensi" => &mut g.potensi,
+        "dirasakan" => &mut g.dirasakan,
+        "shakemap" => &mut g.shakemap,
+        x => unreachable!("unknown field {}", x),
+    }
+}
+
+fn combine(reports: &[(Url, &Gempa)]) -> MergedEvent {
+    let mut gempa = Gempa::new();
+    let mut provenance = BTreeMap::new();
+    let mut reports: Vec<(Url, Gempa)> = reports
+        .iter()
+        .map(|(url, g)| (*url, (*g).clone()))
+        .collect();
+    let sources = reports.iter().map(|(url, _)| *url).collect();
+
+    for group in FIELD_GROUPS.iter() {
+        let source = reports
+            .iter_mut()
+            .position(|(_, g)| group.iter().any(|f| field(g, f).is_some()));
+        if let Some(idx) = source {
+            let (url, g) = &mut reports[idx];
+            for f in group.iter() {
+                let value = field(g, f).take();
+                if value.is_some() {
+                    provenance.insert(f.to_string(), *url);
+                }
+                *field(&mut gempa, f) = value;
+            }
+        }
+    }
+
+    MergedEvent {
+        gempa,
+        sources,
+        provenance,
+    }
+}
+
+/// Merges the reports of several feeds, newest first.
+pub fn merge<'a, I>(feeds: I, config: &Config) -> Vec<MergedEvent>
+where
+    I: IntoIterator<Item = (Url, &'a [Gempa])>,
+{
+    let mut reports: Vec<(Url, &Gempa)> = feeds
+        .into_iter()
+        .flat_map(|(url, data)| data.iter().map(move |g| (url, g)))
+        .collect();
+    reports.sort_by_key(|(url, _)| config.rank(*url));
+
+    // each group starts with its highest-precedence report
+    let mut groups: Vec<Vec<(Url, &Gempa)>> = Vec::new();
+    for (url, g) in reports {
+        let nearest = groups
+            .iter_mut()
+            .filter(|group| group.iter().all(|(u, _)| *u != url))
+            .filter(|group| config.same_event(group[0].1, g))
+            .min_by_key(|group| {
+                group[0]
+                    .1
+                    .time()
+                    .zip(g.time())
+                    .map(|(a, b)| (a - b).num_seconds().abs())
+            });
+        match nearest {
+            Some(group) => group.push((url, g)),
+            None => groups.push(vec![(url, g)]),
+        }
+    }
+
+    let mut merged: Vec<MergedEvent> = groups.iter().map(|group| combine(group)).collect();
+    merged.sort_by_key(|m| std::cmp::Reverse(m.gempa.time()));
+    merged
+}
+
+/// Merged events of the feeds that could be fetched, and the error of each
+/// feed that could not.
+#[derive(Debug)]
+pub struct Fetched {
+    pub events: Vec<MergedEvent>,
+    pub errors: Vec<(Url, Error)>,
+}
+
+fn merge_fetched(results: Vec<(Url, Result<Vec<Gempa>, Error>)>, config: &Config) -> Fetched {
+    let mut feeds = Vec::new();
+    let mut errors = Vec::new();
+    for (url, res) in results {
+        match res {
+            Ok(data) => feeds.push((url, data)),
+            Err(e) => errors.push((url, e)),
+        }
+    }
+
+    let events = merge(
+        feeds.iter().map(|(url, data)| (*url, data.as_slice())),
+        config,
+    );
+    Fetched { events, errors }
+}
+
+/// Fetches all three feeds concurrently and merges those that succeeded.
+pub async fn get_data(config: &Config) -> Fetched {
+    let urls = [Url::Autogempa, Url::GempaTerkini, Url::GempaDirasakan];
+    let results = join_all(urls.iter().map(|url| super::get_data(*url))).await;
+
+    merge_fetched(urls.iter().cloned().zip(results).collect(), config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+
+    fn merged(config: &Config) -> Vec<MergedEvent> {
+        let autogempa = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+        let terkini = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let dirasakan = parse_earthquakes_file("fixtures/gempadirasakan.xml").unwrap();
+
+        merge(
+            vec![
+                (Url::Autogempa, autogempa.as_slice()),
+                (Url::GempaTerkini, terkini.as_slice()),
+                (Url::GempaDirasakan, dirasakan.as_slice()),
+            ],
+            config,
+        )
+    }
+
+    fn find<'a>(events: &'a [MergedEvent], wilayah: &str) -> &'a MergedEvent {
+        events
+            .iter()
+            .find(|m| m.gempa.wilayah.as_ref().unwrap().contains(wilayah))
+            .unwrap()
+    }
+
+    #[test]
+    fn merge_test() {
+        let events = merged(&Config::default());
+        let times: Vec<_> = events.iter().map(|m| m.gempa.time()).collect();
+
+        // 8 + 5 + 1 reports of 10 distinct events
+        assert_eq!(events.len(), 10);
+        assert!(times.windows(2).all(|w| w[0] >= w[1]));
+
+        let sikka = find(&events, "45 km BaratDaya KAB-SIKKA");
+        assert_eq!(
+            sikka.sources,
+            vec![Url::GempaDirasakan, Url::Autogempa, Url::GempaTerkini]
+        );
+        // the later Sikka event two hours on is not the same one
+        assert_eq!(
+            find(&events, "50 km BaratDaya KAB-SIKKA").sources,
+            vec![Url::GempaTerkini]
+        );
+    }
+
+    #[test]
+    fn merge_provenance_test() {
+        let events = merged(&Config::default());
+
+        let sikka = find(&events, "45 km BaratDaya KAB-SIKKA");
+        assert_eq!(sikka.source("magnitude"), Some(Url::GempaDirasakan));
+        assert_eq!(sikka.source("potensi"), Some(Url::Autogempa));
+        assert_eq!(sikka.source("shakemap"), Some(Url::Autogempa));
+        assert!(sikka.gempa.shakemap.is_some());
+        assert_eq!(sikka.gempa.felt_intensity(), Some(3));
+        assert_eq!(sikka.gempa.tsunami_potential(), Some(false));
+
+        // reported 2 s and 0.1 magnitude apart
+        let bantul = find(&events, "BANTUL");
+        assert_eq!(bantul.sources, vec![Url::GempaDirasakan, Url::GempaTerkini]);
+        assert_eq!(bantul.gempa.mag(), Some(5.1));
+        assert_eq!(bantul.source("datetime"), Some(Url::GempaDirasakan));
+        assert_eq!(bantul.source("potensi"), Some(Url::GempaTerkini));
+        assert_eq!(bantul.source("shakemap"), None);
+
+        let json = bantul.clone().to_json();
+        assert_eq!(json["provenance"]["potensi"], "gempaterkini");
+    }
+
+    #[test]
+    fn merge_precedence_test() {
+        let config = Config {
+            precedence: vec![Url::GempaTerkini],
+            ..Config::default()
+        };
+        let bantul = find(&merged(&config), "BANTUL").clone();
+
+        assert_eq!(bantul.gempa.mag(), Some(5.0));
+        assert_eq!(bantul.source("dirasakan"), Some(Url::GempaDirasakan));
+    }
+
+    #[test]
+    fn merge_tolerance_test() {
+        let config = Config {
+            time_tolerance_secs: 1,
+            ..Config::default()
+        };
+        let events = merged(&config);
+
+        assert_eq!(events.len(), 11);
+        assert!(events
+            .iter()
+            .filter(|m| m.gempa.wilayah.as_ref().unwrap().contains("BANTUL"))
+            .all(|m| m.sources.len() == 1));
+    }
+
+    #[test]
+    fn merge_fetched_test() {
+        let autogempa = parse_earthquakes_file("fixtures/autogempa.xml");
+        let dirasakan = parse_earthquakes_file("fixtures/gempadirasakan.xml");
+        let failed = Err(Error::Others("feed unavailable".to_string()));
+
+        let fetched = merge_fetched(
+            vec![
+                (Url::Autogempa, autogempa),
+                (Url::GempaTerkini, failed),
+                (Url::GempaDirasakan, dirasakan),
+            ],
+            &Config::default(),
+        );
+
+        assert_eq!(fetched.errors.len(), 1);
+        assert_eq!(fetched.errors[0].0, Url::GempaTerkini);
+        assert!(!fetched.events.is_empty());
+        assert!(fetched
+            .events
+            .iter()
+            .all(|m| m.sources.iter().all(|u| *u != Url::GempaTerkini)));
+    }
+}
@@ -0,0 +1,190 @@
+//! QuakeML 1.2 export.
+//!
+//! Each earthquake becomes an `event` with one origin and one magnitude.
+//! Public IDs are `smi:<authority>/<kind>/<event key>`, so they stay the
+//! same across exports of the same event.
+
+use super::Gempa;
+use crate::escape_xml;
+
+pub const QUAKEML_NAMESPACE: &str = "http://quakeml.org/xmlns/quakeml/1.2";
+pub const BED_NAMESPACE: &str = "http://quakeml.org/xmlns/bed/1.2";
+pub const DEFAULT_AUTHORITY: &str = "bmkgw";
+const AGENCY_ID: &str = "BMKG";
+
+/// Public ID of `kind` ("event", "origin", "magnitude") for event `key`.
+pub fn public_id(authority: &str, kind: &str, key: &str) -> String {
+    format!("smi:{}/{}/{}", authority, kind, key)
+}
+
+fn event(g: &Gempa, authority: &str) -> Option<String> {
+    let key = g.event_key()?;
+    let time = g.time()?;
+    let (lat, lon) = (g.latitude()?, g.longitude()?);
+    let event_id = public_id(authority, "event", &key);
+    let origin_id = public_id(authority, "origin", &key);
+    let magnitude_id = public_id(authority, "magnitude", &key);
+
+    let mut out = format!("<event publicID=\"{}\">\n", escape_xml(event_id));
+    out.push_str(&format!(
+        "<preferredOriginID>{}</preferredOriginID>\n",
+        escape_xml(origin_id.as_str())
+    ));
+    if g.mag().is_some() {
+        out.push_str(&format!(
+            "<preferredMagnitudeID>{}</preferredMagnitudeID>\n",
+            escape_xml(magnitude_id.as_str())
+        ));
+    }
+    out.push_str("<type>earthquake</type>\n");
+    if let Some(ref wilayah) = g.wilayah {
+        out.push_str(&format!(
+            "<description><text>{}</text><type>region name</type></description>\n",
+            escape_xml(wilayah.trim())
+        ));
+    }
+    out.push_str(&format!(
+        "<creationInfo><agencyID>{}</agencyID></creationInfo>\n",
+        AGENCY_ID
+    ));
+
+    out.push_str(&format!(
+        "<origin publicID=\"{}\">\n",
+        escape_xml(origin_id.as_str())
+    ));
+    out.push_str(&format!(
+        "<time><value>{}</value></time>\n",
+        time.format("%Y-%m-%dT%H:%M:%SZ")
+    ));
+    out.push_str(&format!("<latitude><value>{}</value></latitude>\n", lat));
+    out.push_str(&format!("<longitude><value>{}</value></longitude>\n", lon));
+    if let Some(depth) = g.depth_km() {
+        // QuakeML depths are in metres
+        out.push_str(&format!(
+            "<depth><value>{}</value></depth>\n",
+            depth * 1000.0
+        ));
+    }
+    out.push_str("</origin>\n");
+
+    if let Some(mag) = g.mag() {
+        out.push_str(&format!(
+            "<magnitude publicID=\"{}\">\n",
+            escape_xml(magnitude_id.as_str())
+        ));
+        out.push_str(&format!("<mag><value>{}</value></mag>\n", mag));
+        out.push_str("<type>M</type>\n");
+        out.push_str(&format!("<originID>{}</originID>\n", escape_xml(origin_id)));
+        out.push_str("</magnitude>\n");
+    }
+
+    out.push_str("</event>\n");
+    Some(out)
+}
+
+/// QuakeML document with an `eventParameters` holding every earthquake
+/// whose origin time and coordinates are known; public IDs use `authority`.
+pub fn to_quakeml(data: &[Gempa], authority: &str) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<q:quakeml xmlns:q=\"{}\" xmlns=\"{}\">\n",
+        QUAKEML_NAMESPACE, BED_NAMESPACE
+    ));
+    out.push_str(&format!(
+        "<eventParameters publicID=\"smi:{}/eventParameters\">\n",
+        escape_xml(authority)
+    ));
+    for e in data.iter().filter_map(|g| event(g, authority)) {
+        out.push_str(&e);
+    }
+    out.push_str("</eventParameters>\n</q:quakeml>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    /// `(path, text)` of every element with text, path joined with "/".
+    fn texts(xml: &str) -> Vec<(String, String)> {
+        let mut reader = Reader::from_str(xml);
+        reader.trim_text(true);
+        let mut buf = Vec::new();
+        let mut path: Vec<String> = Vec::new();
+        let mut out = Vec::new();
+        loop {
+            match reader.read_event(&mut buf).unwrap() {
+                Event::Start(ref e) => path.push(String::from_utf8_lossy(e.name()).into_owned()),
+                Event::End(_) => {
+                    path.pop();
+                }
+                Event::Text(ref e) => {
+                    out.push((path.join("/"), e.unescape_and_decode(&reader).unwrap()));
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+            buf.clear();
+        }
+        out
+    }
+
+    fn values<'a>(texts: &'a [(String, String)], path: &str) -> Vec<&'a str> {
+        texts
+            .iter()
+            .filter(|(p, _)| p.ends_with(path))
+            .map(|(_, t)| t.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn to_quakeml_test() {
+        let data = parse_earthquakes_file("fixtures/autogempa.xml").unwrap();
+        let xml = to_quakeml(&data, DEFAULT_AUTHORITY);
+        let texts = texts(&xml);
+
+        assert!(xml.contains("<q:quakeml xmlns:q=\"http://quakeml.org/xmlns/quakeml/1.2\""));
+        assert!(xml.contains("<event publicID=\"smi:bmkgw/event/20211018231544\">"));
+        assert_eq!(
+            values(&texts, "origin/time/value"),
+            vec!["2021-10-18T23:15:44Z"]
+        );
+        assert_eq!(values(&texts, "origin/latitude/value"), vec!["-8.88"]);
+        assert_eq!(values(&texts, "origin/longitude/value"), vec!["121.95"]);
+        assert_eq!(values(&texts, "origin/depth/value"), vec!["10000"]);
+        assert_eq!(values(&texts, "magnitude/mag/value"), vec!["5.2"]);
+        assert_eq!(
+            values(&texts, "event/description/text"),
+            vec!["45 km BaratDaya KAB-SIKKA"]
+        );
+        assert_eq!(
+            values(&texts, "magnitude/originID"),
+            values(&texts, "event/preferredOriginID")
+        );
+    }
+
+    #[test]
+    fn public_ids_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let mut partial = data.clone();
+        partial[0].datetime = None;
+        partial[0].tanggal = None;
+
+        let ids = |xml: &str| -> Vec<String> {
+            values(&texts(xml), "event/preferredOriginID")
+                .iter()
+                .map(|x| x.to_string())
+                .collect()
+        };
+        let all = ids(&to_quakeml(&data, "id.bmkg"));
+        let some = ids(&to_quakeml(&partial, "id.bmkg"));
+
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[1], "smi:id.bmkg/origin/20211018231544");
+        // the event without origin time is left out, the others keep their ids
+        assert_eq!(some, all[1..].to_vec());
+    }
+}
@@ -0,0 +1,390 @@
+//! Magnitude–frequency statistics over earthquake collections.
+//!
+//! Magnitudes are binned on multiples of the bin width (bin `5.2` holds
+//! magnitudes in `[5.15, 5.25)` for a width of 0.1). The Gutenberg–Richter
+//! relation `log10 N(M ≥ m) = a − b·m` is fitted by maximum likelihood
+//! (Aki 1965, with Utsu's correction for binned magnitudes) above a
+//! completeness magnitude, by default the maximum-curvature estimate.
+
+use super::Gempa;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_MAGNITUDE_BIN: f64 = 0.1;
+pub const DEFAULT_DEPTH_BIN_KM: f64 = 10.0;
+/// Smallest accepted bin widths. Together with the physical ranges below
+/// they bound the number of bins.
+pub const MIN_MAGNITUDE_BIN: f64 = 0.01;
+pub const MIN_DEPTH_BIN_KM: f64 = 0.1;
+/// Magnitudes and depths outside these ranges are ignored as bogus.
+pub const MIN_MAGNITUDE: f64 = -3.0;
+pub const MAX_MAGNITUDE: f64 = 10.0;
+pub const MAX_DEPTH_KM: f64 = 1000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    pub magnitude_bin: f64,
+    pub depth_bin_km: f64,
+    /// Completeness magnitude, estimated by maximum curvature when unset.
+    pub completeness: Option<f64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            magnitude_bin: DEFAULT_MAGNITUDE_BIN,
+            depth_bin_km: DEFAULT_DEPTH_BIN_KM,
+            completeness: None,
+        }
+    }
+}
+
+fn valid_width(width: f64, min: f64) -> bool {
+    width.is_finite() && width >= min
+}
+
+fn valid_magnitude(m: f64) -> bool {
+    (MIN_MAGNITUDE..=MAX_MAGNITUDE).contains(&m)
+}
+
+impl Config {
+    /// Rejects bin widths that are not finite or below [`MIN_MAGNITUDE_BIN`]
+    /// and [`MIN_DEPTH_BIN_KM`].
+    pub fn validate(&self) -> Result<(), Error> {
+        if !valid_width(self.magnitude_bin, MIN_MAGNITUDE_BIN) {
+            let msg = format!("invalid magnitude bin width {}", self.magnitude_bin);
+            return Err(Error::Others(msg));
+        }
+        if !valid_width(self.depth_bin_km, MIN_DEPTH_BIN_KM) {
+            let msg = format!("invalid depth bin width {}", self.depth_bin_km);
+            return Err(Error::Others(msg));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MagnitudeBin {
+    /// Bin center.
+    pub magnitude: f64,
+    pub count: usize,
+    /// Number of events in this bin or above.
+    pub cumulative: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepthBin {
+    pub min_km: f64,
+    pub max_km: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GutenbergRichter {
+    /// Completeness magnitude the fit starts at.
+    pub completeness: f64,
+    pub a: f64,
+    pub b: f64,
+    /// Standard error of `b` (Aki 1965), `b / √n`.
+    pub b_error: f64,
+    /// Number of events at or above the completeness magnitude.
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    /// Events with a magnitude in the physical range.
+    pub count: usize,
+    pub magnitude_min: Option<f64>,
+    pub magnitude_max: Option<f64>,
+    pub magnitude_bins: Vec<MagnitudeBin>,
+    pub depth_bins: Vec<DepthBin>,
+    pub gutenberg_richter: Option<GutenbergRichter>,
+}
+
+impl Statistics {
+    /// Fails when `config` does not pass [`Config::validate`].
+    pub fn new(data: &[Gempa], config: &Config) -> Result<Self, Error> {
+        config.validate()?;
+        let magnitudes: Vec<f64> = data
+            .iter()
+            .filter_map(|g| g.mag())
+            .filter(|m| valid_magnitude(*m))
+            .collect();
+        let completeness = config
+            .completeness
+            .or_else(|| max_curvature(&magnitudes, config.magnitude_bin));
+
+        Ok(Self {
+            count: magnitudes.len(),
+            magnitude_min: magnitudes.iter().cloned().reduce(f64::min),
+            magnitude_max: magnitudes.iter().cloned().reduce(f64::max),
+            magnitude_bins: magnitude_frequency(&magnitudes, config.magnitude_bin),
+            depth_bins: depth_histogram(data, config.depth_bin_km),
+            gutenberg_richter: completeness
+                .and_then(|mc| gutenberg_richter(&magnitudes, mc, config.magnitude_bin)),
+        })
+    }
+
+    pub fn to_json(self) -> JsonValue {
+        json!(self)
+    }
+}
+
+fn bin_index(x: f64, width: f64) -> i64 {
+    // nudge so that e.g. 5.15 / 0.1 = 51.4999… still lands in bin 52
+    (x / width + 1e-9).round() as i64
+}
+
+/// Round to the bin width's decimals so that centers print as 5.2, not 5.200000000000001.
+fn bin_value(idx: i64, width: f64) -> f64 {
+    (idx as f64 * width * 1e6).round() / 1e6
+}
+
+/// Non-cumulative and cumulative counts per magnitude bin, every bin from
+/// the smallest to the largest magnitude included. Magnitudes outside
+/// [`MIN_MAGNITUDE`]..=[`MAX_MAGNITUDE`] are ignored. Empty for a bin width
+/// [`Config::validate`] would reject.
+pub fn magnitude_frequency(magnitudes: &[f64], bin_width: f64) -> Vec<MagnitudeBin> {
+    if !valid_width(bin_width, MIN_MAGNITUDE_BIN) {
+        return Vec::new();
+    }
+    let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+    for m in magnitudes.iter().filter(|m| valid_magnitude(**m)) {
+        *counts.entry(bin_index(*m, bin_width)).or_default() += 1;
+    }
+    let (first, last) = match (counts.keys().next(), counts.keys().next_back()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Vec::new(),
+    };
+
+    let mut bins: Vec<MagnitudeBin> = (first..=last)
+        .map(|idx| MagnitudeBin {
+            magnitude: bin_value(idx, bin_width),
+            count: counts.get(&idx).cloned().unwrap_or_default(),
+            cumulative: 0,
+        })
+        .collect();
+    let mut cumulative = 0;
+    for bin in bins.iter_mut().rev() {
+        cumulative += bin.count;
+        bin.cumulative = cumulative;
+    }
+    bins
+}
+
+/// Completeness magnitude by maximum curvature: the center of the most
+/// populated bin, the lower one on ties.
+pub fn max_curvature(magnitudes: &[f64], bin_width: f64) -> Option<f64> {
+    let bins = magnitude_frequency(magnitudes, bin_width);
+    let max = bins.iter().map(|b| b.count).max()?;
+    bins.iter().find(|b| b.count == max).map(|b| b.magnitude)
+}
+
+/// Maximum-likelihood Gutenberg–Richter fit above `completeness`:
+/// `b = log10(e) / (mean(M) − (Mc − ΔM/2))` and `a = log10 N + b·Mc`.
+/// `None` with fewer than two events at or above `completeness`.
+pub fn gutenberg_richter(
+    magnitudes: &[f64],
+    completeness: f64,
+    bin_width: f64,
+) -> Option<GutenbergRichter> {
+    if !valid_width(bin_width, MIN_MAGNITUDE_BIN) || !completeness.is_finite() {
+        return None;
+    }
+    let mc_idx = bin_index(completeness, bin_width);
+    let complete: Vec<f64> = magnitudes
+        .iter()
+        .cloned()
+        .filter(|m| valid_magnitude(*m) && bin_index(*m, bin_width) >= mc_idx)
+        .collect();
+    let n = complete.len();
+    if n < 2 {
+        return None;
+    }
+
+    let mean = complete.iter().sum::<f64>() / n as f64;
+    let spread = mean - (completeness - bin_width / 2.0);
+    if spread <= 0.0 {
+        return None;
+    }
+    let b = std::f64::consts::LOG10_E / spread;
+
+    Some(GutenbergRichter {
+        completeness,
+        a: (n as f64).log10() + b * completeness,
+        b,
+        b_error: b / (n as f64).sqrt(),
+        count: n,
+    })
+}
+
+/// Event counts per depth interval `[min_km, max_km)`, every interval from
+/// the surface to the deepest event included. Depths beyond
+/// [`MAX_DEPTH_KM`] are ignored. Empty for a bin width [`Config::validate`]
+/// would reject.
+pub fn depth_histogram(data: &[Gempa], bin_width_km: f64) -> Vec<DepthBin> {
+    if !valid_width(bin_width_km, MIN_DEPTH_BIN_KM) {
+        return Vec::new();
+    }
+    let depths: Vec<f64> = data
+        .iter()
+        .filter_map(|g| g.depth_km())
+        .filter(|d| d.is_finite() && *d <= MAX_DEPTH_KM)
+        .collect();
+    let deepest = match depths.iter().cloned().reduce(f64::max) {
+        Some(d) => d.max(0.0),
+        None => return Vec::new(),
+    };
+    let n = (deepest / bin_width_km).floor() as usize + 1;
+
+    let mut bins: Vec<DepthBin> = (0..n)
+        .map(|i| DepthBin {
+            min_km: i as f64 * bin_width_km,
+            max_km: (i + 1) as f64 * bin_width_km,
+            count: 0,
+        })
+        .collect();
+    for d in depths {
+        let idx = ((d.max(0.0) / bin_width_km).floor() as usize).min(n - 1);
+        bins[idx].count += 1;
+    }
+    bins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+
+    /// Magnitudes following Gutenberg–Richter with `b` above `mc`, rounded to 0.1.
+    fn synthetic(n: usize, mc: f64, b: f64) -> Vec<f64> {
+        (0..n)
+            .map(|i| {
+                let u = (i as f64 + 0.5) / n as f64;
+                let m = (mc - 0.05) - u.log10() / b;
+                (m * 10.0).round() / 10.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn magnitude_frequency_test() {
+        let bins = magnitude_frequency(&[5.0, 5.2, 5.2, 5.3, 5.6], 0.1);
+        let summary: Vec<(f64, usize, usize)> = bins
+            .iter()
+            .map(|b| (b.magnitude, b.count, b.cumulative))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (5.0, 1, 5),
+                (5.1, 0, 4),
+                (5.2, 2, 4),
+                (5.3, 1, 2),
+                (5.4, 0, 1),
+                (5.5, 0, 1),
+                (5.6, 1, 1)
+            ]
+        );
+        assert!(magnitude_frequency(&[], 0.1).is_empty());
+    }
+
+    #[test]
+    fn gutenberg_richter_test() {
+        let magnitudes = synthetic(5000, 3.0, 1.0);
+        let gr = gutenberg_richter(&magnitudes, 3.0, 0.1).unwrap();
+
+        assert_eq!(gr.count, 5000);
+        assert!((gr.b - 1.0).abs() < 0.03, "b = {}", gr.b);
+        assert!((gr.a - (5000f64.log10() + gr.b * 3.0)).abs() < 1e-9);
+        assert!((gr.b_error - gr.b / 5000f64.sqrt()).abs() < 1e-12);
+
+        // incomplete events below Mc are ignored
+        let mut with_noise = magnitudes.clone();
+        with_noise.extend([2.0, 2.1, 2.5]);
+        assert_eq!(gutenberg_richter(&with_noise, 3.0, 0.1).unwrap(), gr);
+        assert!(gutenberg_richter(&[5.0], 3.0, 0.1).is_none());
+    }
+
+    #[test]
+    fn max_curvature_test() {
+        let mut magnitudes = synthetic(1000, 3.0, 1.0);
+        // an incomplete tail below 3.0
+        magnitudes.extend([2.8, 2.9, 2.9]);
+
+        assert_eq!(max_curvature(&magnitudes, 0.1), Some(3.0));
+        assert_eq!(max_curvature(&[], 0.1), None);
+    }
+
+    #[test]
+    fn statistics_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        let stats = Statistics::new(&data, &Config::default()).unwrap();
+
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.magnitude_min, Some(5.0));
+        assert_eq!(stats.magnitude_max, Some(6.4));
+        assert_eq!(stats.magnitude_bins[0].cumulative, 8);
+        // depths 10, 12, 15, 25, 33, 40, 65, 80 km
+        let depth: Vec<usize> = stats.depth_bins.iter().map(|b| b.count).collect();
+        assert_eq!(depth, vec![0, 3, 1, 1, 1, 0, 1, 0, 1]);
+
+        let gr = stats.gutenberg_richter.unwrap();
+        assert_eq!(gr.completeness, 5.0);
+        assert_eq!(gr.count, 8);
+
+        let json = Statistics::new(&data, &Config::default())
+            .unwrap()
+            .to_json();
+        assert_eq!(json["magnitude_bins"][2]["magnitude"], 5.2);
+    }
+
+    #[test]
+    fn invalid_bin_width_test() {
+        let data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        for (magnitude_bin, depth_bin_km) in [
+            (0.0, 10.0),
+            (f64::NAN, 10.0),
+            (0.1, 0.0),
+            (0.1, -5.0),
+            (0.1, f64::INFINITY),
+            (0.1, 1e-12),
+        ] {
+            let config = Config {
+                magnitude_bin,
+                depth_bin_km,
+                completeness: None,
+            };
+            assert!(config.validate().is_err());
+            assert!(Statistics::new(&data, &config).is_err());
+        }
+
+        assert!(depth_histogram(&data, 0.0).is_empty());
+        assert!(magnitude_frequency(&[5.0], 0.0).is_empty());
+        assert!(gutenberg_richter(&[5.0, 5.1], 5.0, f64::NAN).is_none());
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn out_of_range_values_test() {
+        let mut data = parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap();
+        data[0].magnitude = Some("1e9".to_string());
+        data[1].kedalaman = Some("1e9 km".to_string());
+        let config = Config {
+            magnitude_bin: MIN_MAGNITUDE_BIN,
+            depth_bin_km: MIN_DEPTH_BIN_KM,
+            completeness: None,
+        };
+        let stats = Statistics::new(&data, &config).unwrap();
+
+        assert_eq!(stats.count, 7);
+        assert!(stats.magnitude_max.unwrap() <= MAX_MAGNITUDE);
+        assert!(stats.magnitude_bins.len() <= 141);
+        assert!(stats.depth_bins.len() <= 801);
+        assert_eq!(magnitude_frequency(&[-1e9, 5.0, 1e9], 0.01).len(), 1);
+    }
+}
@@ -0,0 +1,25 @@
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Great-circle distance in kilometres between two points in decimal degrees.
+pub fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_km_test() {
+        // Jakarta to Bandung
+        let d = distance_km(-6.2, 106.8167, -6.9147, 107.6098);
+
+        assert!((d - 118.0).abs() < 2.0);
+        assert_eq!(distance_km(-8.0, 110.0, -8.0, 110.0), 0.0);
+    }
+}
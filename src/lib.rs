@@ -1,14 +1,132 @@
+use chrono::FixedOffset;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
 use std::fmt;
+use std::fs;
+use std::path::Path;
 
+pub mod cache;
 pub mod cuaca;
+#[cfg(feature = "exporter")]
+pub mod exporter;
 pub mod gempa;
+pub mod geo;
+pub mod notify;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod warning;
 
 const BMKG_BASE_URL: &str = "https://data.bmkg.go.id";
 
+/// Indonesian civil time zones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeZone {
+    /// Waktu Indonesia Barat, UTC+7.
+    WIB,
+    /// Waktu Indonesia Tengah, UTC+8.
+    WITA,
+    /// Waktu Indonesia Timur, UTC+9.
+    WIT,
+}
+
+impl TimeZone {
+    pub fn utc_offset(&self) -> FixedOffset {
+        let hours = match self {
+            TimeZone::WIB => 7,
+            TimeZone::WITA => 8,
+            TimeZone::WIT => 9,
+        };
+        FixedOffset::east_opt(hours * 3600).unwrap()
+    }
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str<T: Borrow<str>>(s: T) -> Option<TimeZone> {
+        match s.borrow().trim().to_uppercase().as_ref() {
+            "WIB" => Some(TimeZone::WIB),
+            "WITA" => Some(TimeZone::WITA),
+            "WIT" => Some(TimeZone::WIT),
+            _ => None,
+        }
+    }
+    /// Time zone of a province given by name, e.g. "Provinsi Jawa Barat",
+    /// "DKI Jakarta", "NusaTenggaraTimur" or "sumatera_barat". Spaces,
+    /// underscores and case are ignored; unknown names give `None`.
+    pub fn from_region<T: Borrow<str>>(s: T) -> Option<TimeZone> {
+        let s: String = s
+            .borrow()
+            .to_lowercase()
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '_')
+            .collect();
+        let s = s.trim_start_matches("provinsi");
+
+        let wit = ["maluku", "papua"];
+        let wita = [
+            "bali",
+            "nusatenggara",
+            "ntb",
+            "ntt",
+            "sulawesi",
+            "gorontalo",
+            "kalimantanselatan",
+            "kalimantantimur",
+            "kalimantanutara",
+            "kalsel",
+            "kaltim",
+            "kaltara",
+        ];
+        let wib = [
+            "aceh",
+            "sumatera",
+            "sumatra",
+            "riau",
+            "kepri",
+            "jambi",
+            "bengkulu",
+            "bangka",
+            "belitung",
+            "lampung",
+            "banten",
+            "jakarta",
+            "dki",
+            "jawa",
+            "jabar",
+            "jateng",
+            "jatim",
+            "yogyakarta",
+            "diy",
+            "kalimantanbarat",
+            "kalimantantengah",
+            "kalbar",
+            "kalteng",
+        ];
+
+        if wit.iter().any(|x| s.contains(x)) {
+            Some(TimeZone::WIT)
+        } else if wita.iter().any(|x| s.contains(x)) {
+            Some(TimeZone::WITA)
+        } else if wib.iter().any(|x| s.contains(x)) {
+            Some(TimeZone::WIB)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     ReqwestError(reqwest::Error),
     XmlError(quick_xml::Error),
+    IoError(std::io::Error),
+    JsonError(serde_json::Error),
+    TomlError(toml::de::Error),
+    CsvError(csv::Error),
+    #[cfg(feature = "sqlite")]
+    SqliteError(rusqlite::Error),
+    #[cfg(feature = "arrow")]
+    ArrowError(arrow::error::ArrowError),
+    #[cfg(feature = "arrow")]
+    ParquetError(parquet::errors::ParquetError),
     Others(String),
 }
 
@@ -17,6 +135,16 @@ impl fmt::Display for Error {
         match *self {
             Error::ReqwestError(ref x) => write!(f, "{}", x),
             Error::XmlError(ref x) => write!(f, "{}", x),
+            Error::IoError(ref x) => write!(f, "{}", x),
+            Error::JsonError(ref x) => write!(f, "{}", x),
+            Error::TomlError(ref x) => write!(f, "{}", x),
+            Error::CsvError(ref x) => write!(f, "{}", x),
+            #[cfg(feature = "sqlite")]
+            Error::SqliteError(ref x) => write!(f, "{}", x),
+            #[cfg(feature = "arrow")]
+            Error::ArrowError(ref x) => write!(f, "{}", x),
+            #[cfg(feature = "arrow")]
+            Error::ParquetError(ref x) => write!(f, "{}", x),
             Error::Others(ref x) => write!(f, "{}", x),
         }
     }
@@ -36,3 +164,105 @@ macro_rules! error_wrap {
 
 error_wrap!(reqwest::Error, Error::ReqwestError);
 error_wrap!(quick_xml::Error, Error::XmlError);
+error_wrap!(std::io::Error, Error::IoError);
+error_wrap!(serde_json::Error, Error::JsonError);
+error_wrap!(toml::de::Error, Error::TomlError);
+error_wrap!(csv::Error, Error::CsvError);
+#[cfg(feature = "sqlite")]
+error_wrap!(rusqlite::Error, Error::SqliteError);
+#[cfg(feature = "arrow")]
+error_wrap!(arrow::error::ArrowError, Error::ArrowError);
+#[cfg(feature = "arrow")]
+error_wrap!(parquet::errors::ParquetError, Error::ParquetError);
+
+/// Reads a `.toml` or `.json` configuration file, chosen by extension.
+pub(crate) fn read_config<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, Error> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)?;
+
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("toml") => Ok(toml::from_str(&content)?),
+        Some("json") => Ok(serde_json::from_str(&content)?),
+        _ => Err(Error::Others(format!(
+            "unsupported config file {}",
+            path.display()
+        ))),
+    }
+}
+
+/// Reads the Unix timestamp in column `idx`, failing on values chrono
+/// cannot represent instead of substituting a time.
+#[cfg(feature = "sqlite")]
+pub(crate) fn sql_timestamp(
+    row: &rusqlite::Row,
+    idx: usize,
+) -> rusqlite::Result<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+
+    let secs: i64 = row.get(idx)?;
+    chrono::Utc
+        .timestamp_opt(secs, 0)
+        .single()
+        .ok_or(rusqlite::Error::IntegralValueOutOfRange(idx, secs))
+}
+
+/// Escapes text for XML content and attribute values.
+pub(crate) fn escape_xml<T: Borrow<str>>(s: T) -> String {
+    let s = s.borrow();
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_zone_from_region_test() {
+        assert_eq!(
+            TimeZone::from_region("Provinsi Jawa Barat"),
+            Some(TimeZone::WIB)
+        );
+        assert_eq!(
+            TimeZone::from_region("Kalimantan Timur"),
+            Some(TimeZone::WITA)
+        );
+        assert_eq!(TimeZone::from_region("Maluku Utara"), Some(TimeZone::WIT));
+        assert_eq!(TimeZone::from_region("Provinsi Indonesia"), None);
+        assert_eq!(
+            TimeZone::from_region("NusaTenggaraTimur"),
+            Some(TimeZone::WITA)
+        );
+        assert_eq!(TimeZone::from_region("sumatera_barat"), Some(TimeZone::WIB));
+        assert_eq!(TimeZone::from_region("local"), None);
+    }
+
+    #[test]
+    fn time_zone_utc_offset_test() {
+        assert_eq!(
+            TimeZone::from_str("wita")
+                .unwrap()
+                .utc_offset()
+                .local_minus_utc(),
+            8 * 3600
+        );
+    }
+
+    #[test]
+    fn escape_xml_test() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">Tom & 'Jerry'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,596 @@
+use crate::cuaca::alert::Alert;
+use crate::gempa::alert::Match;
+use crate::gempa::Gempa;
+use crate::{read_config, Error};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// Header carrying `sha256=<hex HMAC of the body>` when an endpoint has a secret.
+pub const SIGNATURE_HEADER: &str = "X-Bmkgw-Signature";
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+fn default_seen_retention_days() -> i64 {
+    30
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature header, unsigned when missing.
+    pub secret: Option<String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+}
+
+impl Endpoint {
+    pub fn new<T: Into<String>>(url: T) -> Self {
+        Self {
+            url: url.into(),
+            secret: None,
+            timeout_secs: default_timeout_secs(),
+            max_retries: default_max_retries(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    pub endpoints: Vec<Endpoint>,
+    /// File failed deliveries are appended to, one JSON object per line.
+    pub dead_letter: Option<PathBuf>,
+    /// Delay before the first retry, doubled on each further attempt.
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// Days an earthquake or forecast alert is remembered after it last
+    /// appeared.
+    #[serde(default = "default_seen_retention_days")]
+    pub seen_retention_days: i64,
+}
+
+impl Config {
+    /// Loads a `.toml` or `.json` file, chosen by extension.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        read_config(path)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Earthquake(Gempa),
+    EarthquakeAlert(Match),
+    ForecastAlert(Alert),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Payload {
+    pub sent_at: DateTime<Utc>,
+    pub event: Event,
+}
+
+/// Outcome of delivering one payload to one endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub url: String,
+    pub attempts: u32,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    /// Why a failed delivery could not be written to the dead-letter log.
+    #[serde(default)]
+    pub dead_letter_error: Option<String>,
+}
+
+impl Delivery {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn forecast_alert_key(a: &Alert) -> String {
+    format!(
+        "forecast/{}/{}/{}/{}",
+        a.rule_id,
+        a.area_id.as_deref().unwrap_or_default(),
+        a.datetime.map(|d| d.timestamp()).unwrap_or_default(),
+        a.h.as_deref().unwrap_or_default()
+    )
+}
+
+/// POSTs events as JSON to every configured endpoint.
+pub struct Notifier {
+    client: reqwest::Client,
+    config: Config,
+    /// Earthquake and forecast alert keys and when they last appeared.
+    seen: HashMap<String, DateTime<Utc>>,
+}
+
+impl Notifier {
+    pub fn new(config: Config) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Marks earthquakes as already known without notifying, e.g. on start-up.
+    pub fn prime(&mut self, quakes: &[Gempa]) {
+        let now = Utc::now();
+        self.seen
+            .extend(quakes.iter().filter_map(Gempa::event_key).map(|k| (k, now)));
+        self.prune_seen(now);
+    }
+
+    /// Sends an `earthquake` event for every quake not seen before, keyed by
+    /// [`Gempa::event_key`]. Events without a key are skipped.
+    ///
+    /// A quake only counts as seen once an endpoint accepted it, so one that
+    /// failed everywhere is sent again on the next call. Keys that have not
+    /// appeared for `seen_retention_days` are forgotten.
+    pub async fn notify_new_earthquakes(&mut self, quakes: &[Gempa]) -> Vec<Delivery> {
+        let now = Utc::now();
+        let mut deliveries = Vec::new();
+
+        for g in quakes {
+            let key = match g.event_key() {
+                Some(key) => key,
+                None => continue,
+            };
+            let event = Event::Earthquake(g.clone());
+            deliveries.extend(self.send_unseen(key, event, now).await);
+        }
+        self.prune_seen(now);
+
+        deliveries
+    }
+
+    /// Sends `event` unless `key` was seen, and marks `key` as seen once at
+    /// least one endpoint accepted it.
+    async fn send_unseen(
+        &mut self,
+        key: String,
+        event: Event,
+        now: DateTime<Utc>,
+    ) -> Vec<Delivery> {
+        if let Some(last) = self.seen.get_mut(&key) {
+            *last = now;
+            return Vec::new();
+        }
+
+        let deliveries = self.send(event).await;
+        if deliveries.iter().any(Delivery::is_ok) {
+            self.seen.insert(key, now);
+        }
+        deliveries
+    }
+
+    fn prune_seen(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - ChronoDuration::days(self.config.seen_retention_days);
+        self.seen.retain(|_, last| *last >= cutoff);
+    }
+
+    pub async fn notify_earthquake_alerts(&self, matches: &[Match]) -> Vec<Delivery> {
+        let mut deliveries = Vec::new();
+        for m in matches {
+            deliveries.extend(self.send(Event::EarthquakeAlert(m.clone())).await);
+        }
+        deliveries
+    }
+
+    /// Sends a `forecast_alert` event for every alert not seen before,
+    /// keyed by rule, area and time range, so re-evaluating the same
+    /// forecast does not repeat them. Seen keys are handled as in
+    /// [`Notifier::notify_new_earthquakes`].
+    pub async fn notify_forecast_alerts(&mut self, alerts: &[Alert]) -> Vec<Delivery> {
+        let now = Utc::now();
+        let mut deliveries = Vec::new();
+
+        for a in alerts {
+            let key = forecast_alert_key(a);
+            let event = Event::ForecastAlert(a.clone());
+            deliveries.extend(self.send_unseen(key, event, now).await);
+        }
+        self.prune_seen(now);
+
+        deliveries
+    }
+
+    /// Delivers one event to all endpoints, recording failures in the
+    /// dead-letter log. A failure to write the log is reported in
+    /// [`Delivery::dead_letter_error`].
+    pub async fn send(&self, event: Event) -> Vec<Delivery> {
+        let payload = Payload {
+            sent_at: Utc::now(),
+            event,
+        };
+        let body = serde_json::to_vec(&payload).expect("payload is serializable");
+        let mut deliveries = Vec::new();
+
+        for endpoint in self.config.endpoints.iter() {
+            let mut delivery = self.deliver(endpoint, &body).await;
+            if !delivery.is_ok() {
+                if let Err(e) = self.dead_letter(&delivery, &payload).await {
+                    delivery.dead_letter_error = Some(e.to_string());
+                }
+            }
+            deliveries.push(delivery);
+        }
+
+        deliveries
+    }
+
+    async fn deliver(&self, endpoint: &Endpoint, body: &[u8]) -> Delivery {
+        let mut delivery = Delivery {
+            url: endpoint.url.clone(),
+            attempts: 0,
+            status: None,
+            error: None,
+            dead_letter_error: None,
+        };
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_ms);
+
+        loop {
+            delivery.attempts += 1;
+
+            let mut request = self
+                .client
+                .post(&endpoint.url)
+                .timeout(Duration::from_secs(endpoint.timeout_secs))
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_vec());
+            if let Some(ref secret) = endpoint.secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret, body));
+            }
+
+            let retry = match request.send().await {
+                Ok(res) => {
+                    let status = res.status();
+                    delivery.status = Some(status.as_u16());
+                    if status.is_success() {
+                        delivery.error = None;
+                        return delivery;
+                    }
+                    delivery.error = Some(format!("unexpected status {}", status));
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => {
+                    delivery.status = None;
+                    delivery.error = Some(e.to_string());
+                    true
+                }
+            };
+
+            if !retry || delivery.attempts > endpoint.max_retries {
+                return delivery;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    async fn dead_letter(&self, delivery: &Delivery, payload: &Payload) -> Result<(), Error> {
+        let path = match self.config.dead_letter {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut line = serde_json::to_vec(&json!({
+            "failed_at": Utc::now(),
+            "delivery": delivery,
+            "payload": payload,
+        }))?;
+        line.push(b'\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(&line).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gempa::parse_earthquakes_file;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    #[derive(Debug, Clone)]
+    struct Request {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    /// Minimal HTTP server answering with `statuses` in turn (the last one
+    /// repeated), after waiting `delay`.
+    async fn stand_in(statuses: Vec<u16>, delay: Duration) -> (String, Arc<Mutex<Vec<Request>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+
+        tokio::spawn(async move {
+            let mut n = 0;
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let (head_len, content_length) = loop {
+                    let read = socket.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..read]);
+                    if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        let head = String::from_utf8_lossy(&buf[..pos]).to_string();
+                        let length = head
+                            .lines()
+                            .find_map(|l| {
+                                l.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        break (pos + 4, length);
+                    }
+                };
+                while buf.len() < head_len + content_length {
+                    let read = socket.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..read]);
+                }
+                let head = String::from_utf8_lossy(&buf[..head_len]).to_string();
+                let headers = head
+                    .lines()
+                    .skip(1)
+                    .filter_map(|l| l.split_once(':'))
+                    .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                    .collect();
+                recorded.lock().unwrap().push(Request {
+                    headers,
+                    body: buf[head_len..head_len + content_length].to_vec(),
+                });
+
+                let status = statuses[n.min(statuses.len() - 1)];
+                n += 1;
+                tokio::time::sleep(delay).await;
+                let response = format!(
+                    "HTTP/1.1 {} Stand-in\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn config(endpoint: Endpoint, dead_letter: Option<PathBuf>) -> Config {
+        Config {
+            endpoints: vec![endpoint],
+            dead_letter,
+            retry_backoff_ms: 10,
+            seen_retention_days: default_seen_retention_days(),
+        }
+    }
+
+    fn quakes() -> Vec<Gempa> {
+        parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap()
+    }
+
+    #[test]
+    fn sign_test() {
+        // RFC 4231 test case 2
+        let signature = sign("Jefe", b"what do ya want for nothing?");
+
+        assert_eq!(
+            signature,
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn send_signed_payload_test() {
+        let (url, requests) = stand_in(vec![200], Duration::from_millis(0)).await;
+        let mut endpoint = Endpoint::new(url);
+        endpoint.secret = Some("s3cret".to_string());
+        let notifier = Notifier::new(config(endpoint, None));
+
+        let deliveries = notifier.send(Event::Earthquake(quakes()[0].clone())).await;
+        let requests = requests.lock().unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+
+        assert!(deliveries[0].is_ok());
+        assert_eq!(deliveries[0].status, Some(200));
+        assert_eq!(
+            requests[0].header(SIGNATURE_HEADER),
+            Some(sign("s3cret", &requests[0].body).as_str())
+        );
+        assert_eq!(body["event"]["type"], "earthquake");
+        assert_eq!(body["event"]["magnitude"], "5.0");
+    }
+
+    #[tokio::test]
+    async fn retry_until_success_test() {
+        let (url, requests) = stand_in(vec![503, 500, 200], Duration::from_millis(0)).await;
+        let notifier = Notifier::new(config(Endpoint::new(url), None));
+
+        let deliveries = notifier.send(Event::Earthquake(quakes()[0].clone())).await;
+
+        assert!(deliveries[0].is_ok());
+        assert_eq!(deliveries[0].attempts, 3);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        assert!(requests.lock().unwrap()[0]
+            .header(SIGNATURE_HEADER)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn client_error_is_not_retried_test() {
+        let (url, requests) = stand_in(vec![400], Duration::from_millis(0)).await;
+        let notifier = Notifier::new(config(Endpoint::new(url), None));
+
+        let deliveries = notifier.send(Event::Earthquake(quakes()[0].clone())).await;
+
+        assert_eq!(deliveries[0].attempts, 1);
+        assert_eq!(deliveries[0].status, Some(400));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn timeout_and_dead_letter_test() {
+        let (url, _) = stand_in(vec![200], Duration::from_secs(3)).await;
+        let path =
+            std::env::temp_dir().join(format!("bmkgw-dead-letter-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut endpoint = Endpoint::new(url);
+        endpoint.timeout_secs = 1;
+        endpoint.max_retries = 1;
+        let notifier = Notifier::new(config(endpoint, Some(path.clone())));
+
+        let deliveries = notifier.send(Event::Earthquake(quakes()[0].clone())).await;
+        let log = std::fs::read_to_string(&path).unwrap();
+        let line: serde_json::Value = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+
+        assert!(!deliveries[0].is_ok());
+        assert_eq!(deliveries[0].attempts, 2);
+        assert_eq!(deliveries[0].dead_letter_error, None);
+        assert_eq!(log.lines().count(), 1);
+        assert_eq!(line["delivery"]["attempts"], 2);
+        assert_eq!(line["payload"]["event"]["type"], "earthquake");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dead_letter_error_test() {
+        let (url, _) = stand_in(vec![400], Duration::from_millis(0)).await;
+        let path = std::env::temp_dir()
+            .join(format!("bmkgw-no-such-dir-{}", std::process::id()))
+            .join("dead.jsonl");
+        let notifier = Notifier::new(config(Endpoint::new(url), Some(path)));
+
+        let deliveries = notifier.send(Event::Earthquake(quakes()[0].clone())).await;
+
+        assert!(!deliveries[0].is_ok());
+        assert!(deliveries[0].dead_letter_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn notify_new_earthquakes_test() {
+        let (url, requests) = stand_in(vec![200], Duration::from_millis(0)).await;
+        let mut notifier = Notifier::new(config(Endpoint::new(url), None));
+        let quakes = quakes();
+        notifier.prime(&quakes[1..]);
+
+        assert_eq!(notifier.notify_new_earthquakes(&quakes).await.len(), 1);
+        assert!(notifier.notify_new_earthquakes(&quakes).await.is_empty());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_earthquake_is_retried_test() {
+        let (url, requests) = stand_in(vec![400, 200], Duration::from_millis(0)).await;
+        let mut notifier = Notifier::new(config(Endpoint::new(url), None));
+        let quakes = quakes();
+        notifier.prime(&quakes[1..]);
+
+        let failed = notifier.notify_new_earthquakes(&quakes).await;
+        assert!(!failed[0].is_ok());
+        assert!(notifier.notify_new_earthquakes(&quakes).await[0].is_ok());
+        assert!(notifier.notify_new_earthquakes(&quakes).await.is_empty());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn notify_forecast_alerts_test() {
+        let (url, requests) = stand_in(vec![200], Duration::from_millis(0)).await;
+        let mut notifier = Notifier::new(config(Endpoint::new(url), None));
+        let alert = Alert {
+            rule_id: "hot".to_string(),
+            area_id: Some("501195".to_string()),
+            area_name: Some("Jakarta Pusat".to_string()),
+            parameter: "t".to_string(),
+            datetime: Some(Utc::now()),
+            h: Some("6".to_string()),
+            value: 35.0,
+            unit: Some("C".to_string()),
+        };
+        let mut later = alert.clone();
+        later.h = Some("12".to_string());
+        let first = vec![alert.clone()];
+        let both = vec![alert, later];
+
+        assert_eq!(notifier.notify_forecast_alerts(&first).await.len(), 1);
+        assert!(notifier.notify_forecast_alerts(&first).await.is_empty());
+        assert_eq!(notifier.notify_forecast_alerts(&both).await.len(), 1);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn prune_seen_test() {
+        let mut notifier = Notifier::new(config(Endpoint::new("http://localhost/hook"), None));
+        let quakes = quakes();
+        notifier.prime(&quakes);
+        assert_eq!(notifier.seen.len(), 8);
+
+        notifier.prune_seen(Utc::now() + ChronoDuration::days(29));
+        assert_eq!(notifier.seen.len(), 8);
+
+        notifier.prune_seen(Utc::now() + ChronoDuration::days(31));
+        assert!(notifier.seen.is_empty());
+    }
+
+    #[test]
+    fn config_from_file_test() {
+        let path = std::env::temp_dir().join(format!("bmkgw-notify-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "dead_letter = \"dead.jsonl\"\n[[endpoints]]\nurl = \"http://localhost/hook\"\nsecret = \"abc\"\n",
+        )
+        .unwrap();
+        let config = Config::from_file(&path).unwrap();
+
+        assert_eq!(config.retry_backoff_ms, 500);
+        assert_eq!(config.endpoints[0].max_retries, 3);
+        assert_eq!(config.endpoints[0].secret, Some("abc".to_string()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,351 @@
+//! REST API over the crate's fetching and caching, enabled by the `server` feature.
+//!
+//! | Route | Response |
+//! |---|---|
+//! | `GET /gempa/{autogempa,terkini,dirasakan}` | `[Gempa]` |
+//! | `GET /cuaca/{province}` | `Data` |
+//! | `GET /cuaca/{province}/areas/{id}` | `Area` |
+//! | `GET /cuaca/nearest?lat=&lon=` | `Nearest` |
+//! | `GET /openapi.json` | OpenAPI 3 document |
+
+use crate::cache::Cache;
+use crate::cuaca::{Area, Data, Province};
+use crate::gempa::{Gempa, Url};
+use crate::Error;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures_util::future::join_all;
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn not_found<T: Into<String>>(message: T) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        let status = match e {
+            Error::ReqwestError(_) | Error::XmlError(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self {
+            status,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Nearest {
+    pub distance_km: f64,
+    pub area: Area,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Position {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+fn feed(s: &str) -> Option<Url> {
+    match s {
+        "terkini" => Some(Url::GempaTerkini),
+        "dirasakan" => Some(Url::GempaDirasakan),
+        x => Url::from_str(x),
+    }
+}
+
+fn province(s: &str) -> Result<Province, ApiError> {
+    Province::from_str(s).ok_or_else(|| ApiError::not_found(format!("unknown province {}", s)))
+}
+
+async fn get_gempa(
+    State(cache): State<Arc<Cache>>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<Gempa>>, ApiError> {
+    let url = feed(&name).ok_or_else(|| ApiError::not_found(format!("unknown feed {}", name)))?;
+    let data = cache.gempa(url).await?;
+    Ok(Json(data.as_ref().clone()))
+}
+
+async fn get_cuaca(
+    State(cache): State<Arc<Cache>>,
+    Path(name): Path<String>,
+) -> Result<Json<Data>, ApiError> {
+    let data = cache.cuaca(province(&name)?).await?;
+    Ok(Json(data.as_ref().clone()))
+}
+
+async fn get_area(
+    State(cache): State<Arc<Cache>>,
+    Path((name, id)): Path<(String, String)>,
+) -> Result<Json<Area>, ApiError> {
+    let data = cache.cuaca(province(&name)?).await?;
+    data.forecast
+        .iter()
+        .flat_map(|f| f.areas.iter())
+        .find(|a| a.id.as_deref() == Some(id.as_str()))
+        .map(|a| Json(a.clone()))
+        .ok_or_else(|| ApiError::not_found(format!("unknown area {}", id)))
+}
+
+async fn get_nearest(
+    State(cache): State<Arc<Cache>>,
+    Query(position): Query<Position>,
+) -> Result<Json<Nearest>, ApiError> {
+    let provinces = Province::all()
+        .into_iter()
+        .filter(|p| *p != Province::Indonesia);
+    let results = join_all(provinces.map(|p| cache.cuaca(p))).await;
+
+    let mut last_error = None;
+    let mut nearest: Option<Nearest> = None;
+    for result in results {
+        let data = match result {
+            Ok(data) => data,
+            Err(e) => {
+                last_error = Some(e);
+                continue;
+            }
+        };
+        for area in data.forecast.iter().flat_map(|f| f.areas.iter()) {
+            let distance_km = match area.distance_km(position.lat, position.lon) {
+                Some(d) => d,
+                None => continue,
+            };
+            if nearest.as_ref().is_none_or(|n| distance_km < n.distance_km) {
+                nearest = Some(Nearest {
+                    distance_km,
+                    area: area.clone(),
+                });
+            }
+        }
+    }
+
+    match (nearest, last_error) {
+        (Some(n), _) => Ok(Json(n)),
+        (None, Some(e)) => Err(e.into()),
+        (None, None) => Err(ApiError::not_found("no area found")),
+    }
+}
+
+async fn get_openapi() -> Json<JsonValue> {
+    Json(openapi())
+}
+
+/// OpenAPI 3 document of the routes, with schemas generated from the crate's types.
+pub fn openapi() -> JsonValue {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let gempa = gen.subschema_for::<Vec<Gempa>>();
+    let data = gen.subschema_for::<Data>();
+    let area = gen.subschema_for::<Area>();
+    let nearest = gen.subschema_for::<Nearest>();
+    let schemas = gen.take_definitions();
+
+    let ok = |schema: &schemars::schema::Schema| {
+        json!({
+            "200": {
+                "description": "OK",
+                "content": { "application/json": { "schema": schema } }
+            },
+            "404": { "description": "Unknown feed, province or area" },
+            "502": { "description": "BMKG could not be reached or returned invalid data" }
+        })
+    };
+    let path_param = |name: &str, description: &str| {
+        json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "description": description,
+            "schema": { "type": "string" }
+        })
+    };
+    let query_param = |name: &str| {
+        json!({
+            "name": name,
+            "in": "query",
+            "required": true,
+            "schema": { "type": "number", "format": "double" }
+        })
+    };
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "bmkgw",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": {
+            "/gempa/{feed}": {
+                "get": {
+                    "summary": "Earthquake feed",
+                    "parameters": [
+                        {
+                            "name": "feed",
+                            "in": "path",
+                            "required": true,
+                            "schema": { "type": "string", "enum": ["autogempa", "terkini", "dirasakan"] }
+                        }
+                    ],
+                    "responses": ok(&gempa)
+                }
+            },
+            "/cuaca/nearest": {
+                "get": {
+                    "summary": "Forecast area nearest to a position",
+                    "parameters": [query_param("lat"), query_param("lon")],
+                    "responses": ok(&nearest)
+                }
+            },
+            "/cuaca/{province}": {
+                "get": {
+                    "summary": "Forecast of a province",
+                    "parameters": [path_param("province", "Province key, e.g. dki or jabar")],
+                    "responses": ok(&data)
+                }
+            },
+            "/cuaca/{province}/areas/{id}": {
+                "get": {
+                    "summary": "Forecast of a single area",
+                    "parameters": [
+                        path_param("province", "Province key, e.g. dki or jabar"),
+                        path_param("id", "Area id")
+                    ],
+                    "responses": ok(&area)
+                }
+            }
+        },
+        "components": { "schemas": schemas }
+    })
+}
+
+pub fn router(cache: Arc<Cache>) -> Router {
+    Router::new()
+        .route("/gempa/:feed", get(get_gempa))
+        .route("/cuaca/nearest", get(get_nearest))
+        .route("/cuaca/:province", get(get_cuaca))
+        .route("/cuaca/:province/areas/:id", get(get_area))
+        .route("/openapi.json", get(get_openapi))
+        .with_state(cache)
+}
+
+pub async fn serve(addr: SocketAddr, cache: Arc<Cache>) -> Result<(), Error> {
+    axum::Server::bind(&addr)
+        .serve(router(cache).into_make_service())
+        .await
+        .map_err(|e| Error::Others(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuaca::parse_forecast_file;
+    use crate::gempa::parse_earthquakes_file;
+    use std::time::Duration;
+
+    async fn spawn() -> String {
+        let cache = Cache::new(Duration::from_secs(3600));
+        cache.insert_gempa(
+            Url::GempaTerkini,
+            parse_earthquakes_file("fixtures/gempaterkini.xml").unwrap(),
+        );
+        let data = parse_forecast_file("fixtures/DigitalForecast-Sample.xml").unwrap();
+        for p in Province::all() {
+            let mut data = data.clone();
+            if p != Province::DKI {
+                data.forecast.as_mut().unwrap().areas.clear();
+            }
+            cache.insert_cuaca(p, data);
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router(Arc::new(cache)).into_make_service());
+        tokio::spawn(server);
+
+        format!("http://{}", addr)
+    }
+
+    async fn get_json(url: String) -> (u16, JsonValue) {
+        let res = reqwest::get(&url).await.unwrap();
+        let status = res.status().as_u16();
+        (status, res.json().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn gempa_route_test() {
+        let base = spawn().await;
+        let (status, body) = get_json(format!("{}/gempa/terkini", base)).await;
+        let (unknown, _) = get_json(format!("{}/gempa/lainnya", base)).await;
+
+        assert_eq!(status, 200);
+        assert_eq!(body.as_array().unwrap().len(), 8);
+        assert_eq!(unknown, 404);
+    }
+
+    #[tokio::test]
+    async fn cuaca_routes_test() {
+        let base = spawn().await;
+        let (status, data) = get_json(format!("{}/cuaca/dki", base)).await;
+        let (_, area) = get_json(format!("{}/cuaca/dki/areas/5002220", base)).await;
+        let (missing, _) = get_json(format!("{}/cuaca/dki/areas/1", base)).await;
+        let (unknown, _) = get_json(format!("{}/cuaca/atlantis", base)).await;
+
+        assert_eq!(status, 200);
+        assert_eq!(data["forecast"]["areas"].as_array().unwrap().len(), 2);
+        assert_eq!(area["description"], "Kepulauan Seribu");
+        assert_eq!(missing, 404);
+        assert_eq!(unknown, 404);
+    }
+
+    #[tokio::test]
+    async fn nearest_route_test() {
+        let base = spawn().await;
+        let (status, body) = get_json(format!("{}/cuaca/nearest?lat=-5.7&lon=106.6", base)).await;
+
+        assert_eq!(status, 200);
+        assert_eq!(body["area"]["id"], "5002220");
+        assert!(body["distance_km"].as_f64().unwrap() < 15.0);
+    }
+
+    #[test]
+    fn openapi_test() {
+        let doc = openapi();
+
+        assert_eq!(doc["openapi"], "3.0.3");
+        assert!(doc["paths"]["/cuaca/{province}/areas/{id}"]["get"].is_object());
+        assert!(doc["components"]["schemas"]["Gempa"]["properties"]["magnitude"].is_object());
+        assert_eq!(
+            doc["paths"]["/cuaca/{province}"]["get"]["responses"]["200"]["content"]
+                ["application/json"]["schema"]["$ref"],
+            "#/components/schemas/Data"
+        );
+    }
+}
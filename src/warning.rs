@@ -0,0 +1,773 @@
+//! BMKG nowcast weather warnings ("peringatan dini cuaca").
+//!
+//! BMKG lists current warnings in an RSS index whose items link to CAP 1.2
+//! (Common Alerting Protocol) documents. [`parse_index`] reads the former
+//! and [`parse_alert`] the latter.
+
+use crate::{escape_xml, Error};
+use chrono::{DateTime, FixedOffset};
+use futures_util::future::join_all;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+const BMKG_WWW_URL: &str = "https://www.bmkg.go.id";
+pub const CAP_NAMESPACE: &str = "urn:oasis:names:tc:emergency:cap:1.2";
+
+macro_rules! cap_enum {
+    ($(#[$m:meta])* $name:ident { $($variant:ident => $s:expr),+ $(,)? }) => {
+        $(#[$m])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub enum $name {
+            $(#[serde(rename = $s)] $variant),+
+        }
+
+        impl $name {
+            /// Value as spelled in CAP 1.2.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $s),+
+                }
+            }
+            #[allow(clippy::should_implement_trait)]
+            pub fn from_str<T: Borrow<str>>(s: T) -> Option<Self> {
+                match s.borrow().trim() {
+                    $($s => Some($name::$variant),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+cap_enum!(Status {
+    Actual => "Actual",
+    Exercise => "Exercise",
+    System => "System",
+    Test => "Test",
+    Draft => "Draft",
+});
+
+cap_enum!(MsgType {
+    Alert => "Alert",
+    Update => "Update",
+    Cancel => "Cancel",
+    Ack => "Ack",
+    Error => "Error",
+});
+
+cap_enum!(Scope {
+    Public => "Public",
+    Restricted => "Restricted",
+    Private => "Private",
+});
+
+cap_enum!(Category {
+    Geo => "Geo",
+    Met => "Met",
+    Safety => "Safety",
+    Security => "Security",
+    Rescue => "Rescue",
+    Fire => "Fire",
+    Health => "Health",
+    Env => "Env",
+    Transport => "Transport",
+    Infra => "Infra",
+    Cbrne => "CBRNE",
+    Other => "Other",
+});
+
+cap_enum!(Urgency {
+    Immediate => "Immediate",
+    Expected => "Expected",
+    Future => "Future",
+    Past => "Past",
+    Unknown => "Unknown",
+});
+
+cap_enum!(
+    /// CAP severity, most severe first.
+    Severity {
+        Extreme => "Extreme",
+        Severe => "Severe",
+        Moderate => "Moderate",
+        Minor => "Minor",
+        Unknown => "Unknown",
+    }
+);
+
+cap_enum!(Certainty {
+    Observed => "Observed",
+    Likely => "Likely",
+    Possible => "Possible",
+    Unlikely => "Unlikely",
+    Unknown => "Unknown",
+});
+
+/// Language of the BMKG warning index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Indonesian,
+    English,
+}
+
+impl Language {
+    pub fn index_url(&self) -> String {
+        let lang = match self {
+            Language::Indonesian => "id",
+            Language::English => "en",
+        };
+        format!("{}/alerts/nowcast/{}/rss.xml", BMKG_WWW_URL, lang)
+    }
+}
+
+/// An item of the RSS index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub title: Option<String>,
+    /// URL of the CAP document.
+    pub link: Option<String>,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub pub_date: Option<DateTime<FixedOffset>>,
+}
+
+impl Item {
+    fn new() -> Self {
+        Self {
+            title: None,
+            link: None,
+            description: None,
+            author: None,
+            pub_date: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Circle {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_km: f64,
+}
+
+/// `valueName`/`value` pair of an `info` block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub value_name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Area {
+    pub area_desc: Option<String>,
+    /// Rings of `(latitude, longitude)` points, first and last equal.
+    pub polygons: Vec<Vec<(f64, f64)>>,
+    pub circles: Vec<Circle>,
+}
+
+impl Area {
+    pub(crate) fn new() -> Self {
+        Self {
+            area_desc: None,
+            polygons: Vec::new(),
+            circles: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Info {
+    pub language: Option<String>,
+    pub categories: Vec<Category>,
+    pub event: Option<String>,
+    pub urgency: Option<Urgency>,
+    pub severity: Option<Severity>,
+    pub certainty: Option<Certainty>,
+    pub effective: Option<DateTime<FixedOffset>>,
+    pub onset: Option<DateTime<FixedOffset>>,
+    pub expires: Option<DateTime<FixedOffset>>,
+    pub sender_name: Option<String>,
+    pub headline: Option<String>,
+    pub description: Option<String>,
+    pub instruction: Option<String>,
+    pub web: Option<String>,
+    pub contact: Option<String>,
+    pub parameters: Vec<Parameter>,
+    pub areas: Vec<Area>,
+}
+
+impl Info {
+    pub(crate) fn new() -> Self {
+        Self {
+            language: None,
+            categories: Vec::new(),
+            event: None,
+            urgency: None,
+            severity: None,
+            certainty: None,
+            effective: None,
+            onset: None,
+            expires: None,
+            sender_name: None,
+            headline: None,
+            description: None,
+            instruction: None,
+            web: None,
+            contact: None,
+            parameters: Vec::new(),
+            areas: Vec::new(),
+        }
+    }
+}
+
+/// A CAP 1.2 `<alert>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub identifier: Option<String>,
+    pub sender: Option<String>,
+    pub sent: Option<DateTime<FixedOffset>>,
+    pub status: Option<Status>,
+    pub msg_type: Option<MsgType>,
+    pub scope: Option<Scope>,
+    pub note: Option<String>,
+    pub references: Option<String>,
+    pub infos: Vec<Info>,
+}
+
+impl Alert {
+    pub(crate) fn new() -> Self {
+        Self {
+            identifier: None,
+            sender: None,
+            sent: None,
+            status: None,
+            msg_type: None,
+            scope: None,
+            note: None,
+            references: None,
+            infos: Vec::new(),
+        }
+    }
+    /// The `info` block in `language` (e.g. "id" or "en-US"), matching on
+    /// the primary subtag, otherwise the first one.
+    pub fn info<T: Borrow<str>>(&self, language: T) -> Option<&Info> {
+        let language = language.borrow().to_lowercase();
+        let primary = |x: &str| x.split('-').next().unwrap_or_default().to_lowercase();
+        self.infos
+            .iter()
+            .find(|i| {
+                i.language
+                    .as_deref()
+                    .is_some_and(|l| primary(l) == primary(&language))
+            })
+            .or_else(|| self.infos.first())
+    }
+    /// Serializes the alert as a CAP 1.2 document, elements in schema order.
+    pub fn to_xml(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str(&format!("<alert xmlns=\"{}\">\n", CAP_NAMESPACE));
+        element(&mut out, "identifier", self.identifier.as_ref());
+        element(&mut out, "sender", self.sender.as_ref());
+        element(&mut out, "sent", self.sent.as_ref().map(format_time));
+        element(&mut out, "status", self.status.map(|x| x.as_str()));
+        element(&mut out, "msgType", self.msg_type.map(|x| x.as_str()));
+        element(&mut out, "scope", self.scope.map(|x| x.as_str()));
+        element(&mut out, "note", self.note.as_ref());
+        element(&mut out, "references", self.references.as_ref());
+        for info in self.infos.iter() {
+            info.write_xml(&mut out);
+        }
+        out.push_str("</alert>\n");
+        out
+    }
+}
+
+/// CAP date time: no fractional seconds and a numeric offset, never "Z".
+/// UTC is written as "-00:00" (CAP 1.2 §3.3.2).
+fn format_time(t: &DateTime<FixedOffset>) -> String {
+    if t.offset().local_minus_utc() == 0 {
+        t.format("%Y-%m-%dT%H:%M:%S-00:00").to_string()
+    } else {
+        t.format("%Y-%m-%dT%H:%M:%S%:z").to_string()
+    }
+}
+
+fn element<T: AsRef<str>>(out: &mut String, tag: &str, value: Option<T>) {
+    if let Some(value) = value {
+        out.push_str(&format!(
+            "<{0}>{1}</{0}>\n",
+            tag,
+            escape_xml(value.as_ref())
+        ));
+    }
+}
+
+fn format_point((lat, lon): (f64, f64)) -> String {
+    format!("{},{}", lat, lon)
+}
+
+impl Area {
+    fn write_xml(&self, out: &mut String) {
+        out.push_str("<area>\n");
+        element(
+            out,
+            "areaDesc",
+            Some(self.area_desc.as_deref().unwrap_or_default()),
+        );
+        for polygon in self.polygons.iter() {
+            let points: Vec<String> = polygon.iter().cloned().map(format_point).collect();
+            element(out, "polygon", Some(points.join(" ")));
+        }
+        for c in self.circles.iter() {
+            let circle = format!(
+                "{} {}",
+                format_point((c.latitude, c.longitude)),
+                c.radius_km
+            );
+            element(out, "circle", Some(circle));
+        }
+        out.push_str("</area>\n");
+    }
+}
+
+impl Info {
+    fn write_xml(&self, out: &mut String) {
+        out.push_str("<info>\n");
+        element(out, "language", self.language.as_ref());
+        for category in self.categories.iter() {
+            element(out, "category", Some(category.as_str()));
+        }
+        element(out, "event", self.event.as_ref());
+        element(out, "urgency", self.urgency.map(|x| x.as_str()));
+        element(out, "severity", self.severity.map(|x| x.as_str()));
+        element(out, "certainty", self.certainty.map(|x| x.as_str()));
+        element(out, "effective", self.effective.as_ref().map(format_time));
+        element(out, "onset", self.onset.as_ref().map(format_time));
+        element(out, "expires", self.expires.as_ref().map(format_time));
+        element(out, "senderName", self.sender_name.as_ref());
+        element(out, "headline", self.headline.as_ref());
+        element(out, "description", self.description.as_ref());
+        element(out, "instruction", self.instruction.as_ref());
+        element(out, "web", self.web.as_ref());
+        element(out, "contact", self.contact.as_ref());
+        for p in self.parameters.iter() {
+            out.push_str("<parameter>\n");
+            element(out, "valueName", Some(&p.value_name));
+            element(out, "value", Some(&p.value));
+            out.push_str("</parameter>\n");
+        }
+        for area in self.areas.iter() {
+            area.write_xml(out);
+        }
+        out.push_str("</info>\n");
+    }
+}
+
+fn parse_time(s: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(s.trim()).ok()
+}
+
+/// `"lat,lon lat,lon ..."`
+fn parse_polygon(s: &str) -> Option<Vec<(f64, f64)>> {
+    s.split_whitespace()
+        .map(|pair| {
+            let mut it = pair.split(',');
+            let lat = it.next()?.trim().parse().ok()?;
+            let lon = it.next()?.trim().parse().ok()?;
+            Some((lat, lon))
+        })
+        .collect()
+}
+
+/// `"lat,lon radius"`, radius in km.
+fn parse_circle(s: &str) -> Option<Circle> {
+    let mut parts = s.split_whitespace();
+    let (lat, lon) = parse_polygon(parts.next()?)?.into_iter().next()?;
+    let radius_km = parts.next()?.parse().ok()?;
+    Some(Circle {
+        latitude: lat,
+        longitude: lon,
+        radius_km,
+    })
+}
+
+/// Local name of an element, without namespace prefix.
+fn local_name(name: &[u8]) -> String {
+    let name = String::from_utf8_lossy(name);
+    match name.rsplit_once(':') {
+        Some((_, local)) => local.to_string(),
+        None => name.into_owned(),
+    }
+}
+
+/// Reads elements, calling `f` with the path of local names and the text
+/// of every leaf element.
+fn walk<B: BufRead, F>(inner: B, mut f: F) -> Result<(), Error>
+where
+    F: FnMut(&[String], Option<String>),
+{
+    let mut reader = Reader::from_reader(inner);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) => {
+                path.push(local_name(e.name()));
+                text.clear();
+                f(&path, None);
+            }
+            Event::Empty(ref e) => {
+                path.push(local_name(e.name()));
+                f(&path, None);
+                path.pop();
+            }
+            Event::Text(ref e) | Event::CData(ref e) => {
+                text.push_str(&e.unescape_and_decode(&reader)?)
+            }
+            Event::End(_) => {
+                if !text.is_empty() {
+                    f(&path, Some(text.clone()));
+                    text.clear();
+                }
+                path.pop();
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    Ok(())
+}
+
+fn parse_index_data<B: BufRead>(inner: B) -> Result<Vec<Item>, Error> {
+    let mut items = Vec::new();
+
+    walk(inner, |path, text| {
+        let path: Vec<&str> = path.iter().map(|x| x.as_str()).collect();
+        match (path.as_slice(), text) {
+            ([.., "channel", "item"], None) => items.push(Item::new()),
+            ([.., "item", field], Some(text)) => {
+                let item = match items.last_mut() {
+                    Some(item) => item,
+                    None => return,
+                };
+                match *field {
+                    "title" => item.title = Some(text),
+                    "link" => item.link = Some(text),
+                    "description" => item.description = Some(text),
+                    "author" => item.author = Some(text),
+                    "pubDate" => item.pub_date = DateTime::parse_from_rfc2822(text.trim()).ok(),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    })?;
+
+    Ok(items)
+}
+
+fn parse_alert_data<B: BufRead>(inner: B) -> Result<Alert, Error> {
+    let mut alert = Alert::new();
+    let mut found = false;
+
+    walk(inner, |path, text| {
+        let path: Vec<&str> = path.iter().map(|x| x.as_str()).collect();
+        match (path.as_slice(), text) {
+            (["alert"], None) => found = true,
+            (["alert", "info"], None) => alert.infos.push(Info::new()),
+            (["alert", "info", "area"], None) => {
+                if let Some(info) = alert.infos.last_mut() {
+                    info.areas.push(Area::new());
+                }
+            }
+            (["alert", "info", "parameter"], None) => {
+                if let Some(info) = alert.infos.last_mut() {
+                    info.parameters.push(Parameter {
+                        value_name: String::new(),
+                        value: String::new(),
+                    });
+                }
+            }
+            (["alert", field], Some(text)) => match *field {
+                "identifier" => alert.identifier = Some(text),
+                "sender" => alert.sender = Some(text),
+                "sent" => alert.sent = parse_time(&text),
+                "status" => alert.status = Status::from_str(text),
+                "msgType" => alert.msg_type = MsgType::from_str(text),
+                "scope" => alert.scope = Scope::from_str(text),
+                "note" => alert.note = Some(text),
+                "references" => alert.references = Some(text),
+                _ => (),
+            },
+            (["alert", "info", field], Some(text)) => {
+                let info = match alert.infos.last_mut() {
+                    Some(info) => info,
+                    None => return,
+                };
+                match *field {
+                    "language" => info.language = Some(text),
+                    "category" => info.categories.extend(Category::from_str(text)),
+                    "event" => info.event = Some(text),
+                    "urgency" => info.urgency = Urgency::from_str(text),
+                    "severity" => info.severity = Severity::from_str(text),
+                    "certainty" => info.certainty = Certainty::from_str(text),
+                    "effective" => info.effective = parse_time(&text),
+                    "onset" => info.onset = parse_time(&text),
+                    "expires" => info.expires = parse_time(&text),
+                    "senderName" => info.sender_name = Some(text),
+                    "headline" => info.headline = Some(text),
+                    "description" => info.description = Some(text),
+                    "instruction" => info.instruction = Some(text),
+                    "web" => info.web = Some(text),
+                    "contact" => info.contact = Some(text),
+                    _ => (),
+                }
+            }
+            (["alert", "info", "parameter", field], Some(text)) => {
+                let parameter = alert.infos.last_mut().and_then(|i| i.parameters.last_mut());
+                match (parameter, *field) {
+                    (Some(p), "valueName") => p.value_name = text,
+                    (Some(p), "value") => p.value = text,
+                    _ => (),
+                }
+            }
+            (["alert", "info", "area", field], Some(text)) => {
+                let area = match alert.infos.last_mut().and_then(|i| i.areas.last_mut()) {
+                    Some(area) => area,
+                    None => return,
+                };
+                match *field {
+                    "areaDesc" => area.area_desc = Some(text),
+                    "polygon" => area.polygons.extend(parse_polygon(&text)),
+                    "circle" => area.circles.extend(parse_circle(&text)),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    })?;
+
+    if found {
+        Ok(alert)
+    } else {
+        Err(Error::Others("no CAP alert element".to_string()))
+    }
+}
+
+pub fn parse_index<T: AsRef<[u8]>>(xml: T) -> Result<Vec<Item>, Error> {
+    parse_index_data(xml.as_ref())
+}
+
+pub fn parse_index_reader<R: Read>(r: R) -> Result<Vec<Item>, Error> {
+    parse_index_data(BufReader::new(r))
+}
+
+pub fn parse_index_file<P: AsRef<Path>>(path: P) -> Result<Vec<Item>, Error> {
+    parse_index_reader(File::open(path)?)
+}
+
+pub fn parse_alert<T: AsRef<[u8]>>(xml: T) -> Result<Alert, Error> {
+    parse_alert_data(xml.as_ref())
+}
+
+pub fn parse_alert_reader<R: Read>(r: R) -> Result<Alert, Error> {
+    parse_alert_data(BufReader::new(r))
+}
+
+pub fn parse_alert_file<P: AsRef<Path>>(path: P) -> Result<Alert, Error> {
+    parse_alert_reader(File::open(path)?)
+}
+
+pub async fn get_index(lang: Language) -> Result<Vec<Item>, Error> {
+    let xml = reqwest::get(&lang.index_url()).await?.bytes().await?;
+    parse_index(xml)
+}
+
+pub async fn get_alert(url: &str) -> Result<Alert, Error> {
+    let xml = reqwest::get(url).await?.bytes().await?;
+    parse_alert(xml)
+}
+
+/// Fetches CAP documents concurrently, one result per link in the same order.
+pub async fn get_alerts<I, T>(links: I) -> Vec<Result<Alert, Error>>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<str>,
+{
+    let links: Vec<T> = links.into_iter().collect();
+    join_all(links.iter().map(|l| get_alert(l.as_ref()))).await
+}
+
+/// Fetches the index and every CAP document it links to. Only a failed
+/// index fetch is an error; each document has its own result, so one broken
+/// link does not lose the others.
+pub async fn get_data(lang: Language) -> Result<Vec<Result<Alert, Error>>, Error> {
+    let links = get_index(lang)
+        .await?
+        .into_iter()
+        .filter_map(|item| item.link);
+    Ok(get_alerts(links).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_index_test() {
+        let items = parse_index(include_str!("../fixtures/nowcast_rss.xml")).unwrap();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            items[0].link.as_deref(),
+            Some("https://www.bmkg.go.id/alerts/nowcast/id/CJB20211019002_alert.xml")
+        );
+        assert!(items[0]
+            .description
+            .as_deref()
+            .unwrap()
+            .contains("<b>Kota Bogor</b>"));
+        assert!(items[1]
+            .description
+            .as_deref()
+            .unwrap()
+            .contains("Jakarta Selatan & Jakarta Timur"));
+        assert_eq!(
+            items[1].pub_date.unwrap().to_rfc3339(),
+            "2021-10-19T13:05:00+07:00"
+        );
+    }
+
+    #[test]
+    fn parse_alert_test() {
+        let alert = parse_alert_file("fixtures/nowcast_cap.xml").unwrap();
+
+        assert_eq!(alert.identifier.as_deref(), Some("CJB20211019002"));
+        assert_eq!(alert.status, Some(Status::Actual));
+        assert_eq!(alert.msg_type, Some(MsgType::Alert));
+        assert_eq!(alert.scope, Some(Scope::Public));
+        assert_eq!(alert.infos.len(), 1);
+
+        let info = alert.info("id").unwrap();
+        assert_eq!(info.categories, vec![Category::Met]);
+        assert_eq!(
+            info.event.as_deref(),
+            Some("Hujan Lebat disertai Kilat/Petir dan Angin Kencang")
+        );
+        assert_eq!(info.urgency, Some(Urgency::Immediate));
+        assert_eq!(info.severity, Some(Severity::Moderate));
+        assert_eq!(info.certainty, Some(Certainty::Likely));
+        assert_eq!(
+            info.onset.unwrap().to_rfc3339(),
+            "2021-10-19T14:30:00+07:00"
+        );
+        assert_eq!(
+            info.expires.unwrap().to_rfc3339(),
+            "2021-10-19T17:00:00+07:00"
+        );
+        assert!(info
+            .description
+            .as_deref()
+            .unwrap()
+            .contains("Kab. Sukabumi"));
+    }
+
+    #[test]
+    fn parse_alert_areas_test() {
+        let alert = parse_alert_file("fixtures/nowcast_cap.xml").unwrap();
+        let areas = &alert.infos[0].areas;
+
+        assert_eq!(areas.len(), 2);
+        assert_eq!(areas[0].area_desc.as_deref(), Some("Kota Bogor"));
+        assert_eq!(areas[0].polygons.len(), 1);
+        assert_eq!(areas[0].polygons[0].len(), 5);
+        assert_eq!(areas[0].polygons[0][1], (-6.51, 106.85));
+        assert_eq!(areas[1].polygons.len(), 2);
+        assert_eq!(
+            areas[1].circles,
+            vec![Circle {
+                latitude: -6.6,
+                longitude: 106.8,
+                radius_km: 5.0
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_alert_invalid_test() {
+        assert!(parse_alert("<rss></rss>").is_err());
+        assert!(parse_alert("<alert><identifier>x</alert>").is_err());
+    }
+
+    #[test]
+    fn language_index_url_test() {
+        assert_eq!(
+            Language::English.index_url(),
+            "https://www.bmkg.go.id/alerts/nowcast/en/rss.xml"
+        );
+    }
+
+    #[test]
+    fn to_xml_roundtrip_test() {
+        let alert = parse_alert_file("fixtures/nowcast_cap.xml").unwrap();
+        let xml = alert.to_xml();
+
+        assert!(xml.contains("<sent>2021-10-19T14:20:00+07:00</sent>"));
+        assert!(xml.contains("<circle>-6.6,106.8 5</circle>"));
+        assert_eq!(parse_alert(&xml).unwrap(), alert);
+    }
+
+    #[test]
+    fn format_time_utc_test() {
+        let utc = DateTime::parse_from_rfc3339("2021-10-19T07:20:00+00:00").unwrap();
+        let wib = DateTime::parse_from_rfc3339("2021-10-19T14:20:00+07:00").unwrap();
+
+        assert_eq!(format_time(&utc), "2021-10-19T07:20:00-00:00");
+        assert_eq!(format_time(&wib), "2021-10-19T14:20:00+07:00");
+        assert_eq!(parse_time(&format_time(&utc)), Some(utc));
+    }
+
+    #[tokio::test]
+    async fn get_alerts_keeps_going_test() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/alert.xml", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let body = include_str!("../fixtures/nowcast_cap.xml");
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let _ = socket.read(&mut [0u8; 4096]).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let alerts = get_alerts(vec![
+            url.as_str(),
+            "http://127.0.0.1:1/alert.xml",
+            url.as_str(),
+        ])
+        .await;
+
+        assert_eq!(alerts.len(), 3);
+        assert!(alerts[0].is_ok());
+        assert!(alerts[1].is_err());
+        assert!(alerts[2].is_ok());
+    }
+}